@@ -0,0 +1,174 @@
+use std::fmt::Write;
+
+use crate::error::ExifError;
+use crate::filetype::FileType;
+use crate::jpeg::{EOI, RST0, RST7, SOI, SOS, TEM};
+use crate::mode::ParseMode;
+use crate::png::PNG_SIGNATURE;
+
+// Everything someone would need to reproduce a file we couldn't read, without the picture itself.
+// People are understandably reluctant to attach their photos to a public issue, but the photo was
+// never the problem: what breaks us is the metadata. So we keep the file's metadata, byte for
+// byte, and leave out the image data, and write down what we saw on the way through it (the
+// "trace") and what went wrong. That's two files, bundled up as a tar, since everything can open
+// one.
+//
+// We only know where the image data is in jpegs and PNGs. For anything else we'd be guessing, so
+// rather than risk including some of the picture, we include none of the file, just the trace.
+pub struct BugReport {
+    // What we kept of the file, if anything.
+    pub metadata: Option<Vec<u8>>,
+    pub trace: String,
+}
+
+impl BugReport {
+    // A report on buf, which failed with error. The file's name is left out too, since that can
+    // say more than people would like.
+    pub fn new(buf: &[u8], mode: ParseMode, error: &ExifError) -> Self {
+        let file_type = FileType::from_magic(buf);
+        let mut trace = String::new();
+        let _ = writeln!(trace, "helloexif {} bug report", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(trace, "file type: {}, {} bytes", file_type, buf.len());
+        let _ = writeln!(trace, "mode: {}", mode);
+        let _ = writeln!(trace, "error: {}", error);
+        let _ = writeln!(trace);
+        let metadata = match file_type {
+            FileType::Jpeg => Some(jpeg_metadata(buf, &mut trace)),
+            FileType::Png => Some(png_metadata(buf, &mut trace)),
+            _ => {
+                let _ = writeln!(trace, "We can't tell metadata from image data in {} files, so none of the file is included.", file_type);
+                None
+            }
+        };
+        BugReport { metadata, trace }
+    }
+
+    // The report as a tar file: trace.txt, and metadata.bin if we kept anything.
+    pub fn to_tar(&self) -> Vec<u8> {
+        let mut tar = Vec::new();
+        tar_file(&mut tar, "trace.txt", self.trace.as_bytes());
+        if let Some(metadata) = &self.metadata {
+            tar_file(&mut tar, "metadata.bin", metadata);
+        }
+        // Two empty blocks mark the end of the archive.
+        tar.extend_from_slice(&[0; 1024]);
+        tar
+    }
+}
+
+// A jpeg's segments, up to the first scan, and an EOI to finish it off, so it's still a jpeg as
+// far as any parser is concerned. Metadata has to come before the first scan, so that's all of it.
+// We stop early if the segments stop making sense, rather than copy on past where we can trust
+// the lengths, since we could be copying image data by then.
+fn jpeg_metadata(buf: &[u8], trace: &mut String) -> Vec<u8> {
+    let mut kept = vec![0xff, SOI];
+    let _ = writeln!(trace, "{:#010x} SOI", 0);
+    let mut pos = 2;
+    loop {
+        while buf.get(pos) == Some(&0xff) && buf.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        let marker = match buf.get(pos..pos + 2) {
+            Some(&[0xff, marker]) => marker,
+            Some(_) => {
+                let _ = writeln!(trace, "{:#010x} expected a marker, found {:02x?}, stopping", pos, &buf[pos..pos + 2]);
+                break;
+            },
+            None => {
+                let _ = writeln!(trace, "{:#010x} end of file", pos);
+                break;
+            }
+        };
+        match marker {
+            SOS => {
+                let _ = writeln!(trace, "{:#010x} SOS, image data follows, not included", pos);
+                break;
+            },
+            EOI => {
+                let _ = writeln!(trace, "{:#010x} EOI", pos);
+                break;
+            },
+            TEM | RST0..=RST7 => {
+                let _ = writeln!(trace, "{:#010x} marker ff{:02x}, kept", pos, marker);
+                kept.extend_from_slice(&buf[pos..pos + 2]);
+                pos += 2;
+            },
+            _ => {
+                let length = match buf.get(pos + 2..pos + 4) {
+                    Some(length) => u16::from_be_bytes([length[0], length[1]]) as usize,
+                    None => {
+                        let _ = writeln!(trace, "{:#010x} marker ff{:02x}, file ends before its length", pos, marker);
+                        break;
+                    }
+                };
+                let end = pos + 2 + length;
+                if length < 2 || end > buf.len() {
+                    let _ = writeln!(trace, "{:#010x} marker ff{:02x}, length {} runs past the end of the file ({} bytes), not included", pos, marker, length, buf.len());
+                    break;
+                }
+                let _ = writeln!(trace, "{:#010x} marker ff{:02x}, {} bytes, kept", pos, marker, length);
+                kept.extend_from_slice(&buf[pos..end]);
+                pos = end;
+            }
+        }
+    }
+    kept.extend_from_slice(&[0xff, EOI]);
+    kept
+}
+
+// A PNG's chunks, other than the image data (IDAT, and APNG's fdAT frames). Chunks keep their
+// CRCs, so this is still a valid PNG, just one without a picture.
+fn png_metadata(buf: &[u8], trace: &mut String) -> Vec<u8> {
+    let mut kept = PNG_SIGNATURE.to_vec();
+    let mut pos = PNG_SIGNATURE.len();
+    while let Some(header) = buf.get(pos..pos + 8) {
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_type = String::from_utf8_lossy(&header[4..8]).to_string();
+        let end = match pos.checked_add(length + 12).filter(|&end| end <= buf.len()) {
+            Some(end) => end,
+            None => {
+                let _ = writeln!(trace, "{:#010x} {} chunk, length {} runs past the end of the file, not included", pos, chunk_type, length);
+                return kept;
+            }
+        };
+        if chunk_type == "IDAT" || chunk_type == "fdAT" {
+            let _ = writeln!(trace, "{:#010x} {} chunk, {} bytes of image data, not included", pos, chunk_type, length);
+        } else {
+            let _ = writeln!(trace, "{:#010x} {} chunk, {} bytes, kept", pos, chunk_type, length);
+            kept.extend_from_slice(&buf[pos..end]);
+        }
+        if chunk_type == "IEND" {
+            return kept;
+        }
+        pos = end;
+    }
+    let _ = writeln!(trace, "{:#010x} end of file", pos);
+    kept
+}
+
+// One file in a tar archive: a 512 byte ustar header, then the contents, padded out to a multiple
+// of 512. Numbers in the header are octal text. The checksum is the sum of the header's bytes,
+// counting the checksum field itself as spaces.
+// Ref: https://www.gnu.org/software/tar/manual/html_node/Standard.html
+fn tar_file(tar: &mut Vec<u8>, name: &str, contents: &[u8]) {
+    let mut header = [0u8; 512];
+    let field = |header: &mut [u8; 512], offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    field(&mut header, 0, name.as_bytes());
+    field(&mut header, 100, b"0000644\0");
+    field(&mut header, 108, b"0000000\0");
+    field(&mut header, 116, b"0000000\0");
+    field(&mut header, 124, format!("{:011o}\0", contents.len()).as_bytes());
+    field(&mut header, 136, b"00000000000\0");
+    field(&mut header, 148, b"        ");
+    header[156] = b'0';
+    field(&mut header, 257, b"ustar\0");
+    field(&mut header, 263, b"00");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    field(&mut header, 148, format!("{:06o}\0 ", checksum).as_bytes());
+    tar.extend_from_slice(&header);
+    tar.extend_from_slice(contents);
+    let padding = (512 - contents.len() % 512) % 512;
+    tar.resize(tar.len() + padding, 0);
+}
@@ -0,0 +1,101 @@
+use std::io::prelude::*;
+
+use crate::error::Result;
+use crate::jpeg::find_eoi;
+
+// How much we read at a time, at least. See fill() for why it's "at least".
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// The biggest jpeg we'll try to recover. Anything claiming to be bigger than this is far more
+// likely to be a false start (an ff d8 ff that just happened to turn up in some other file's data)
+// than a real photo, and giving up keeps our memory use bounded.
+pub const MAX_CARVE_SIZE: usize = 64 * 1024 * 1024;
+
+// A jpeg we found, and where we found it.
+pub struct CarvedJpeg {
+    // Byte offset of its SOI marker in the input.
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+// Recovers jpegs from raw data, like an image of a memory card whose filesystem is gone. Deleting
+// a file (or losing the filesystem) usually just forgets where the file was, and the bytes are
+// still sitting on the card, so we do what this crate did from the start: scan raw bytes for a
+// marker. Here that's SOI, the start of a jpeg. From each one we walk the jpeg's segments to find
+// its EOI, the same way we check for truncated image data, and hand back everything in between.
+//
+// This is an Iterator, so it reads the input as it goes, rather than loading a whole disk image
+// into memory. We only ever hold on to the jpeg we're currently looking at.
+pub struct Carver<R: Read> {
+    reader: R,
+    window: Vec<u8>,
+    // Offset in the input of window[0].
+    window_offset: u64,
+}
+
+impl<R: Read> Carver<R> {
+    pub fn new(reader: R) -> Self {
+        Carver { reader, window: Vec::new(), window_offset: 0 }
+    }
+
+    // Read some more of the input onto the end of the window. Returns false once there's nothing
+    // left. We read as much again as we're already holding, so the window doubles each time. That
+    // matters because finding EOI starts from SOI every time, and growing by a fixed amount would
+    // have us re-walking the same megabytes over and over for a big photo.
+    fn fill(&mut self) -> Result<bool> {
+        let wanted = self.window.len().max(CHUNK_SIZE) as u64;
+        let read = (&mut self.reader).take(wanted).read_to_end(&mut self.window)?;
+        Ok(read > 0)
+    }
+
+    // Forget the first n bytes of the window.
+    fn discard(&mut self, n: usize) {
+        self.window.drain(..n);
+        self.window_offset += n as u64;
+    }
+}
+
+impl<R: Read> Iterator for Carver<R> {
+    type Item = Result<CarvedJpeg>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // Look for the start of a jpeg: SOI, and the ff of whatever marker follows it.
+            match self.window.windows(3).position(|bytes| bytes == [0xff, 0xd8, 0xff]) {
+                Some(start) => self.discard(start),
+                None => {
+                    // Keep the last couple of bytes, in case a marker is split across two reads.
+                    let keep = self.window.len().min(2);
+                    self.discard(self.window.len() - keep);
+                    match self.fill() {
+                        Ok(true) => continue,
+                        Ok(false) => return None,
+                        Err(e) => return Some(Err(e))
+                    }
+                }
+            }
+
+            // The window starts with SOI now. Find where it ends, reading more as we need it.
+            loop {
+                match find_eoi(&self.window) {
+                    Ok(Some(end)) => {
+                        let jpeg = CarvedJpeg { offset: self.window_offset, data: self.window[..end].to_vec() };
+                        self.discard(end);
+                        return Some(Ok(jpeg));
+                    },
+                    Ok(None) if self.window.len() < MAX_CARVE_SIZE => match self.fill() {
+                        Ok(true) => continue,
+                        // The input ended partway through.
+                        Ok(false) => break,
+                        Err(e) => return Some(Err(e))
+                    },
+                    // Too big, or the segments stopped making sense. Either way, not a jpeg.
+                    _ => break
+                }
+            }
+
+            // A false start. Skip this SOI and keep looking after it.
+            self.discard(2);
+        }
+    }
+}
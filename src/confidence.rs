@@ -0,0 +1,111 @@
+use std::fmt;
+
+use crate::ifd::Directory;
+use crate::jpeg::EXIF_SIGNATURE;
+use crate::value::type_size;
+
+// How sure we are that what we parsed really is exif, and why. When we find the tiff header by
+// following the file's own structure there's not much doubt, but when we find it by scanning for
+// its signature (which is all find() does) we could have latched on to any four bytes that happen
+// to look right. So we look around for things that should be true of real exif, and keep a note
+// of each one, so whoever's using the result can decide how much to trust it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Confidence {
+    // 0 to 100.
+    pub score: u8,
+    pub evidence: Vec<Evidence>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Evidence {
+    // We found the tiff header by following the file's structure.
+    Structural,
+    // We found the tiff header by scanning for its signature.
+    ByteScan,
+    // The tiff header comes right after "Exif\0\0", like it does in a jpeg's APP1 segment.
+    ExifSignature,
+    // IFD0 has a believable number of entries. Real ones have a dozen or so, never none, and
+    // anything in the hundreds is more likely random bytes.
+    PlausibleEntryCount(usize),
+    ImplausibleEntryCount(usize),
+    // The spec requires entries to be sorted by tag, and writers almost always manage it. Random
+    // bytes almost never are.
+    SortedTags,
+    UnsortedTags,
+    // How many of IFD0's entries have a type that exists.
+    ValidTypes { valid: usize, total: usize },
+}
+
+// Above this many entries, IFD0 gets suspicious.
+const MAX_PLAUSIBLE_ENTRIES: usize = 200;
+
+impl Confidence {
+    // Weigh up the evidence for a tiff header at tiff_header_offset in buf, whose IFD0 is ifd0.
+    //
+    // The weights are a judgement call, not science: a structural find starts at full marks and a
+    // byte scan at half, and everything else nudges the score up or down from there.
+    pub fn assess(buf: &[u8], tiff_header_offset: usize, scanned: bool, ifd0: &Directory) -> Self {
+        let mut evidence = Vec::new();
+        let mut score: i32 = if scanned {
+            evidence.push(Evidence::ByteScan);
+            50
+        } else {
+            evidence.push(Evidence::Structural);
+            100
+        };
+
+        let signature_start = tiff_header_offset.saturating_sub(EXIF_SIGNATURE.len());
+        if tiff_header_offset >= EXIF_SIGNATURE.len() && &buf[signature_start..tiff_header_offset] == EXIF_SIGNATURE {
+            evidence.push(Evidence::ExifSignature);
+            score += 20;
+        }
+
+        let count = ifd0.entries.len();
+        if count > 0 && count <= MAX_PLAUSIBLE_ENTRIES {
+            evidence.push(Evidence::PlausibleEntryCount(count));
+            score += 10;
+        } else {
+            evidence.push(Evidence::ImplausibleEntryCount(count));
+            score -= 30;
+        }
+
+        if ifd0.entries.windows(2).all(|pair| pair[0].tag < pair[1].tag) {
+            evidence.push(Evidence::SortedTags);
+            score += 10;
+        } else {
+            evidence.push(Evidence::UnsortedTags);
+            score -= 10;
+        }
+
+        // Up to 10 points for valid types, and down to -20 for none at all.
+        let valid = ifd0.entries.iter().filter(|entry| type_size(entry.tag_type).is_some()).count();
+        evidence.push(Evidence::ValidTypes { valid, total: count });
+        if let Some(fraction) = (30 * valid).checked_div(count) {
+            score += fraction as i32 - 20;
+        }
+
+        Confidence { score: score.clamp(0, 100) as u8, evidence }
+    }
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Evidence::Structural => write!(f, "found by following the file's structure"),
+            Evidence::ByteScan => write!(f, "found by scanning for a tiff header"),
+            Evidence::ExifSignature => write!(f, "preceded by the Exif signature"),
+            Evidence::PlausibleEntryCount(n) => write!(f, "IFD0 has a plausible {} entries", n),
+            Evidence::ImplausibleEntryCount(n) => write!(f, "IFD0 has an implausible {} entries", n),
+            Evidence::SortedTags => write!(f, "IFD0 tags are sorted"),
+            Evidence::UnsortedTags => write!(f, "IFD0 tags are out of order"),
+            Evidence::ValidTypes { valid, total } => write!(f, "{} of {} IFD0 entries have valid types", valid, total),
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let evidence = self.evidence.iter().map(|e| e.to_string()).collect::<Vec<String>>().join(", ");
+        write!(f, "{}% ({})", self.score, evidence)
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt;
+
+use crate::error::{ExifError, Result};
+use crate::tag::TagId;
+use crate::value::TagValue;
+use crate::Exif;
+
+// Exif has three timestamps, each spread across three tags: the date and time itself, as ASCII
+// "YYYY:MM:DD HH:MM:SS" in the camera's local time, the fraction of a second as ASCII digits, and
+// (since Exif 2.31) the offset from UTC, as "+HH:MM". Ref: https://www.exif.org/Exif2-2.PDF, 4.6.4
+// and 4.6.5, and CIPA DC-008-2016 for the offsets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateTimeKind {
+    // When the file was last changed. DateTime, in IFD0.
+    Modified,
+    // When the photo was taken. DateTimeOriginal.
+    Original,
+    // When it was turned into digital data, which for a digital camera is when it was taken, and
+    // for a scanner, when it was scanned. DateTimeDigitized.
+    Digitized,
+}
+
+impl DateTimeKind {
+    // The date and time, sub-second and offset tags that go together.
+    pub fn tags(&self) -> (TagId, TagId, TagId) {
+        match self {
+            DateTimeKind::Modified => (TagId::DateTime, TagId::SubSecTime, TagId::OffsetTime),
+            DateTimeKind::Original => (TagId::DateTimeOriginal, TagId::SubSecTimeOriginal, TagId::OffsetTimeOriginal),
+            DateTimeKind::Digitized => (TagId::DateTimeDigitized, TagId::SubSecTimeDigitized, TagId::OffsetTimeDigitized),
+        }
+    }
+}
+
+impl fmt::Display for DateTimeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateTimeKind::Modified => write!(f, "modified"),
+            DateTimeKind::Original => write!(f, "taken"),
+            DateTimeKind::Digitized => write!(f, "digitized"),
+        }
+    }
+}
+
+// One of those timestamps, put back together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExifDateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    // Up to 60, for leap seconds.
+    pub second: u8,
+    // From the SubSecTime tag, if there is one.
+    pub nanosecond: Option<u32>,
+    // Minutes east of UTC, from the OffsetTime tag, if there is one. Without it, all we know is
+    // that this is the local time wherever the camera thought it was.
+    pub offset: Option<i16>,
+}
+
+impl ExifDateTime {
+    // Read one of the timestamps. Ok(None) if the file doesn't have it, or has it blanked out, which
+    // the spec allows for when the camera doesn't know. A date that's there but isn't a date is an
+    // error when we're being strict, and left out otherwise, like a blank one. Broken sub-second
+    // and offset tags are just left out.
+    pub fn read(exif: &Exif, kind: DateTimeKind) -> Result<Option<Self>> {
+        let (date_time, sub_sec, offset) = kind.tags();
+        let ascii = |tag: TagId| -> Result<Option<String>> {
+            match exif.entry(tag) {
+                Some(entry) => match exif.value(entry)? {
+                    TagValue::Ascii(s) => Ok(Some(s.into_owned())),
+                    _ => Ok(None)
+                },
+                None => Ok(None)
+            }
+        };
+        let text = match ascii(date_time)? {
+            Some(text) if !is_blank(&text) => text,
+            _ => return Ok(None)
+        };
+        let mut parsed = match ExifDateTime::parse(&text) {
+            Some(parsed) => parsed,
+            None if exif.mode().is_strict() => return Err(ExifError::SpecViolation("date and time isn't YYYY:MM:DD HH:MM:SS")),
+            None => return Ok(None)
+        };
+        parsed.nanosecond = ascii(sub_sec)?.and_then(|s| parse_sub_sec(&s));
+        parsed.offset = ascii(offset)?.and_then(|s| parse_offset(&s));
+        Ok(Some(parsed))
+    }
+
+    // "YYYY:MM:DD HH:MM:SS", and nothing else, though we let trailing whitespace go. Some writers
+    // use dashes in the date, which we let go too.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim_end();
+        let b = s.as_bytes();
+        if b.len() != 19 || !matches!(b[4], b':' | b'-') || b[7] != b[4] || b[10] != b' ' || b[13] != b':' || b[16] != b':' {
+            return None;
+        }
+        let number = |range: std::ops::Range<usize>| -> Option<u16> {
+            let digits = s.get(range)?;
+            if digits.bytes().all(|b| b.is_ascii_digit()) { digits.parse().ok() } else { None }
+        };
+        let year = number(0..4)?;
+        let month = number(5..7)? as u8;
+        let day = number(8..10)? as u8;
+        let hour = number(11..13)? as u8;
+        let minute = number(14..16)? as u8;
+        let second = number(17..19)? as u8;
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        Some(ExifDateTime { year, month, day, hour, minute, second, nanosecond: None, offset: None })
+    }
+
+    // Seconds since 1970-01-01T00:00:00Z, if we know the offset from UTC. Without one, we'd only
+    // be guessing. Leap seconds count as the next second.
+    pub fn unix_timestamp(&self) -> Option<i64> {
+        let offset = self.offset? as i64;
+        Some(self.local_timestamp() - offset * 60)
+    }
+
+    // Seconds since 1970-01-01T00:00:00 on the camera's clock, whatever time zone that was in,
+    // ignoring the offset. Good for putting photos from the same camera in order, or measuring the
+    // time between them, where unix_timestamp would need an offset most cameras don't write.
+    pub fn local_timestamp(&self) -> i64 {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+    }
+}
+
+// Blank, per the spec, is spaces where the digits go, but plenty of cameras write zeros instead.
+fn is_blank(s: &str) -> bool {
+    s.trim().is_empty() || s.chars().all(|c| matches!(c, ' ' | ':' | '0'))
+}
+
+// The digits of a decimal fraction, so "5" and "50" are both half a second. Writers pad with
+// spaces, and we only have room for nanoseconds, so past 9 digits we stop.
+fn parse_sub_sec(s: &str) -> Option<u32> {
+    let digits = s.trim();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits = &digits[..digits.len().min(9)];
+    let scale = 10u32.pow(9 - digits.len() as u32);
+    Some(digits.parse::<u32>().ok()? * scale)
+}
+
+// "+HH:MM" or "-HH:MM" as minutes east of UTC. Blank ones ("   :  ") mean unknown. No time zone is
+// more than 14 hours from UTC, so anything further out is nonsense.
+fn parse_offset(s: &str) -> Option<i16> {
+    let b = s.trim().as_bytes();
+    if b.len() != 6 || b[3] != b':' {
+        return None;
+    }
+    let sign = match b[0] {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None
+    };
+    let hours: i16 = std::str::from_utf8(&b[1..3]).ok()?.parse().ok()?;
+    let minutes: i16 = std::str::from_utf8(&b[4..6]).ok()?.parse().ok()?;
+    if minutes > 59 || hours * 60 + minutes > 14 * 60 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar. This is Howard Hinnant's
+// days_from_civil: http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// ISO 8601, like 2008-05-30T15:56:01.25+09:00, with as much as we know.
+impl fmt::Display for ExifDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)?;
+        if let Some(nanosecond) = self.nanosecond {
+            let fraction = format!("{:09}", nanosecond);
+            let fraction = fraction.trim_end_matches('0');
+            if !fraction.is_empty() {
+                write!(f, ".{}", fraction)?;
+            }
+        }
+        match self.offset {
+            Some(0) => write!(f, "Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                write!(f, "{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+            },
+            None => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_time(s: &str, offset: Option<i16>) -> ExifDateTime {
+        ExifDateTime { offset, ..ExifDateTime::parse(s).unwrap() }
+    }
+
+    #[test]
+    fn leap_years() {
+        assert!(!is_leap_year(1900));
+        assert!(is_leap_year(2000));
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(2023));
+        assert_eq!(ExifDateTime::parse("1900:02:29 12:00:00"), None);
+        assert!(ExifDateTime::parse("2000:02:29 12:00:00").is_some());
+        assert!(ExifDateTime::parse("2024:02:29 12:00:00").is_some());
+        assert_eq!(ExifDateTime::parse("2024:02:30 12:00:00"), None);
+        assert_eq!(days_from_civil(1900, 3, 1), -25508);
+    }
+
+    #[test]
+    fn parses_dates_and_times() {
+        let parsed = ExifDateTime::parse("2008:05:30 15:56:01 ").unwrap();
+        assert_eq!((parsed.year, parsed.month, parsed.day), (2008, 5, 30));
+        assert_eq!((parsed.hour, parsed.minute, parsed.second), (15, 56, 1));
+        assert_eq!(ExifDateTime::parse("2008-05-30 15:56:01"), Some(parsed));
+        assert_eq!(ExifDateTime::parse("2008-05:30 15:56:01"), None);
+        assert_eq!(ExifDateTime::parse("2008:05:30T15:56:01"), None);
+        assert_eq!(ExifDateTime::parse("2008:05:30 24:00:00"), None);
+        assert_eq!(ExifDateTime::parse("2008:+5:30 15:56:01"), None);
+        assert!(ExifDateTime::parse("2016:12:31 23:59:60").is_some());
+    }
+
+    #[test]
+    fn blank_dates() {
+        assert!(is_blank("    :  :     :  :  "));
+        assert!(is_blank("0000:00:00 00:00:00"));
+        assert!(is_blank(""));
+        assert!(!is_blank("2008:05:30 15:56:01"));
+        assert_eq!(ExifDateTime::parse("    :  :     :  :  "), None);
+        assert_eq!(ExifDateTime::parse("0000:00:00 00:00:00"), None);
+    }
+
+    #[test]
+    fn sub_seconds_are_a_fraction() {
+        assert_eq!(parse_sub_sec("5"), Some(500_000_000));
+        assert_eq!(parse_sub_sec("50"), Some(500_000_000));
+        assert_eq!(parse_sub_sec("05"), Some(50_000_000));
+        assert_eq!(parse_sub_sec(" 25  "), Some(250_000_000));
+        assert_eq!(parse_sub_sec("1234567891"), Some(123_456_789));
+        assert_eq!(parse_sub_sec("   "), None);
+        assert_eq!(parse_sub_sec("-5"), None);
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse_offset("+09:00"), Some(540));
+        assert_eq!(parse_offset("-05:30"), Some(-330));
+        assert_eq!(parse_offset("+00:00"), Some(0));
+        assert_eq!(parse_offset("+14:00"), Some(840));
+        assert_eq!(parse_offset("-14:00"), Some(-840));
+        assert_eq!(parse_offset("+14:30"), None);
+        assert_eq!(parse_offset("-15:00"), None);
+        assert_eq!(parse_offset("+09:60"), None);
+        assert_eq!(parse_offset("   :  "), None);
+        assert_eq!(parse_offset("09:00"), None);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(date_time("1970:01:01 00:00:00", Some(0)).unix_timestamp(), Some(0));
+        assert_eq!(date_time("2008:05:30 15:56:01", Some(540)).unix_timestamp(), Some(1212130561));
+        assert_eq!(date_time("2000:03:01 00:59:59", Some(60)).unix_timestamp(), Some(951868799));
+        assert_eq!(date_time("2008:05:30 15:56:01", None).unix_timestamp(), None);
+        assert_eq!(date_time("2008:05:30 15:56:01", None).local_timestamp(), 1212162961);
+    }
+
+    #[test]
+    fn displays_as_iso_8601() {
+        let mut parsed = date_time("2008:05:30 15:56:01", Some(540));
+        parsed.nanosecond = parse_sub_sec("25");
+        assert_eq!(parsed.to_string(), "2008-05-30T15:56:01.25+09:00");
+        assert_eq!(date_time("2008:05:30 15:56:01", Some(-330)).to_string(), "2008-05-30T15:56:01-05:30");
+        assert_eq!(date_time("2008:05:30 15:56:01", Some(0)).to_string(), "2008-05-30T15:56:01Z");
+        assert_eq!(date_time("2008:05:30 15:56:01", None).to_string(), "2008-05-30T15:56:01");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::rational::Rational;
+use crate::tag::TagId;
+use crate::value::TagValue;
+
+// Plenty of tags hold a number that stands for something, like Compression, where 7 means JPEG. The
+// TagValue is the number, since that's what's in the file, and this is where the numbers get
+// turned back into what they stand for, for showing to people.
+
+// Ref: TIFF 6.0 section 3 and 7-10, the TIFF tag registry at
+// https://www.awaresystems.be/imaging/tiff/tifftags/compression.html, and the raw formats that
+// put their own codes in IFD0 (Sony ARW, Nikon NEF, Pentax PEF...).
+pub const COMPRESSIONS: &[(u64, &str)] = &[
+    (1, "uncompressed"),
+    (2, "CCITT modified Huffman RLE"),
+    (3, "CCITT Group 3 fax"),
+    (4, "CCITT Group 4 fax"),
+    (5, "LZW"),
+    (6, "old-style JPEG"),
+    (7, "JPEG"),
+    (8, "Deflate"),
+    (9, "JBIG black and white"),
+    (10, "JBIG color"),
+    (99, "JPEG"),
+    (262, "Kodak 262"),
+    (32766, "NeXT 2-bit RLE"),
+    (32767, "Sony ARW compressed"),
+    (32769, "Epson packed raw"),
+    (32770, "Samsung SRW compressed"),
+    (32771, "CCIRLEW"),
+    (32772, "Samsung SRW compressed 2"),
+    (32773, "PackBits"),
+    (32809, "ThunderScan RLE"),
+    (32867, "Kodak KDC compressed"),
+    (32895, "IT8CTPAD"),
+    (32896, "IT8LW"),
+    (32897, "IT8MP"),
+    (32898, "IT8BL"),
+    (32908, "Pixar film"),
+    (32909, "Pixar log"),
+    (32946, "Deflate"),
+    (32947, "Kodak DCS"),
+    (33003, "Aperio JPEG 2000 YCbCr"),
+    (33005, "Aperio JPEG 2000 RGB"),
+    (34661, "JBIG"),
+    (34676, "SGI log luminance RLE"),
+    (34677, "SGI log 24-bit packed"),
+    (34712, "JPEG 2000"),
+    (34713, "Nikon NEF compressed"),
+    (34715, "JBIG2 TIFF FX"),
+    (34718, "Microsoft Document Imaging binary level codec"),
+    (34719, "Microsoft Document Imaging progressive transform codec"),
+    (34720, "Microsoft Document Imaging vector"),
+    (34887, "ESRI Lerc"),
+    (34892, "lossy JPEG"),
+    (34925, "LZMA2"),
+    (34926, "Zstandard"),
+    (34927, "WebP"),
+    (34933, "PNG"),
+    (34934, "JPEG XR"),
+    (65000, "Kodak DCR compressed"),
+    (65535, "Pentax PEF compressed"),
+];
+
+pub const PHOTOMETRIC_INTERPRETATIONS: &[(u64, &str)] = &[
+    (0, "WhiteIsZero"),
+    (1, "BlackIsZero"),
+    (2, "RGB"),
+    (3, "palette"),
+    (4, "transparency mask"),
+    (5, "CMYK"),
+    (6, "YCbCr"),
+    (8, "CIELab"),
+    (9, "ICCLab"),
+    (10, "ITULab"),
+    (32844, "LogL"),
+    (32845, "LogLuv"),
+    (34892, "linear raw"),
+];
+
+pub const PLANAR_CONFIGURATIONS: &[(u64, &str)] = &[
+    (1, "chunky (samples interleaved)"),
+    (2, "planar (one plane per sample)"),
+];
+
+pub const PREDICTORS: &[(u64, &str)] = &[
+    (1, "none"),
+    (2, "horizontal differencing"),
+    (3, "floating point"),
+];
+
+pub const SAMPLE_FORMATS: &[(u64, &str)] = &[
+    (1, "unsigned integer"),
+    (2, "signed integer"),
+    (3, "IEEE floating point"),
+    (4, "undefined"),
+    (5, "complex integer"),
+    (6, "complex IEEE floating point"),
+];
+
+// A value's name from one of the tables above, or its number if it isn't there.
+pub fn code_name(names: &[(u64, &'static str)], code: u64) -> String {
+    match names.iter().find(|(c, _)| *c == code) {
+        Some((_, name)) => name.to_string(),
+        None => format!("unknown ({})", code)
+    }
+}
+
+// What a tag's value stands for, or None if it's just a number (or a string, or whatever else)
+// that doesn't stand for anything but itself. Tags with one value per sample get one name each.
+pub fn describe(tag: TagId, value: &TagValue) -> Option<String> {
+    let names = match tag {
+        TagId::ExposureTime => return exposure_time(first_rational(value)?),
+        TagId::FNumber => return f_number(first_rational(value)?),
+        TagId::Compression => COMPRESSIONS,
+        TagId::PhotometricInterpretation => PHOTOMETRIC_INTERPRETATIONS,
+        TagId::PlanarConfiguration => PLANAR_CONFIGURATIONS,
+        TagId::Predictor => PREDICTORS,
+        TagId::SampleFormat => SAMPLE_FORMATS,
+        _ => return None
+    };
+    let codes = value.to_u64s()?;
+    Some(codes.iter().map(|&code| code_name(names, code)).collect::<Vec<String>>().join(", "))
+}
+
+fn first_rational(value: &TagValue) -> Option<Rational> {
+    match value {
+        TagValue::Rational(v) => v.first().copied(),
+        _ => None
+    }
+}
+
+// Exposure times the way a camera shows them: fractions of a second as 1/250 s, whatever the
+// writer stored (10/2500, say, or 3/1000, which comes out as the nearest 1/333 s),
+// and anything longer than a quarter second as a plain number, 0.5 s or 30 s.
+fn exposure_time(time: Rational) -> Option<String> {
+    let seconds = time.to_f64()?;
+    let simplified = time.simplify();
+    if seconds > 0.0 && seconds <= 0.25 {
+        if simplified.numerator() == 1 {
+            return Some(format!("1/{} s", simplified.denominator()));
+        }
+        return Some(format!("1/{} s", (1.0 / seconds).round()));
+    }
+    Some(format!("{} s", (seconds * 10.0).round() / 10.0))
+}
+
+// Apertures as f/2.8, to the one decimal place lenses are marked with.
+fn f_number(f_number: Rational) -> Option<String> {
+    Some(format!("f/{:.1}", f_number.to_f64()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exposure(numerator: u32, denominator: u32) -> Option<String> {
+        describe(TagId::ExposureTime, &TagValue::Rational(vec![Rational::new(numerator, denominator)]))
+    }
+
+    fn aperture(numerator: u32, denominator: u32) -> Option<String> {
+        describe(TagId::FNumber, &TagValue::Rational(vec![Rational::new(numerator, denominator)]))
+    }
+
+    #[test]
+    fn exposure_times() {
+        assert_eq!(exposure(1, 250).as_deref(), Some("1/250 s"));
+        assert_eq!(exposure(10, 2500).as_deref(), Some("1/250 s"));
+        assert_eq!(exposure(3, 1000).as_deref(), Some("1/333 s"));
+        assert_eq!(exposure(1, 4).as_deref(), Some("1/4 s"));
+        assert_eq!(exposure(1, 2).as_deref(), Some("0.5 s"));
+        assert_eq!(exposure(30, 1).as_deref(), Some("30 s"));
+        assert_eq!(exposure(0, 1).as_deref(), Some("0 s"));
+        assert_eq!(exposure(1, 0), None);
+    }
+
+    #[test]
+    fn f_numbers() {
+        assert_eq!(aperture(28, 10).as_deref(), Some("f/2.8"));
+        assert_eq!(aperture(56, 10).as_deref(), Some("f/5.6"));
+        assert_eq!(aperture(8, 1).as_deref(), Some("f/8.0"));
+        assert_eq!(aperture(0, 0), None);
+        assert_eq!(describe(TagId::FNumber, &TagValue::Short(vec![8])), None);
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(describe(TagId::Compression, &TagValue::Short(vec![7])).as_deref(), Some("JPEG"));
+        assert_eq!(describe(TagId::SampleFormat, &TagValue::Short(vec![1, 3])).as_deref(), Some("unsigned integer, IEEE floating point"));
+        assert_eq!(describe(TagId::Compression, &TagValue::Short(vec![12345])).as_deref(), Some("unknown (12345)"));
+        assert_eq!(describe(TagId::Make, &TagValue::Short(vec![7])), None);
+    }
+}
@@ -0,0 +1,76 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+use std::str;
+
+use crate::filetype::FileType;
+
+// Everything that can go wrong while reading exif. We used to catch panics from slicing past the
+// end of our buffer, which works, but hides real bugs in with bad files, and prints a scary panic
+// message either way. Now every read is bounds checked, and problems come back as one of these.
+// The &'static strs say what we were trying to read at the time.
+#[derive(Debug)]
+pub enum ExifError {
+    // The data ran out before the thing we were reading did.
+    TruncatedBuffer(&'static str),
+    // An offset points somewhere outside the data.
+    InvalidOffset(&'static str),
+    // A marker, signature or magic number isn't what it should be.
+    BadMarker(&'static str),
+    // A tag type we don't know how to decode.
+    UnsupportedType(u16),
+    // A file format we can recognize, but can't read exif from (yet).
+    UnsupportedFormat(FileType),
+    // We looked, and there's no exif here.
+    NoExif,
+    // A change we can't make to this file, and why.
+    Unwritable(&'static str),
+    // Something we'd normally step around, but were asked not to (see ParseMode::Strict).
+    SpecViolation(&'static str),
+    Utf8(str::Utf8Error),
+    Io(io::Error),
+}
+
+// Shorthand, so signatures can say Result<Exif> rather than Result<Exif, ExifError>.
+pub type Result<T> = result::Result<T, ExifError>;
+
+impl fmt::Display for ExifError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExifError::TruncatedBuffer(what) => write!(f, "Data ends partway through the {}.", what),
+            ExifError::InvalidOffset(what) => write!(f, "The {} offset points outside the data.", what),
+            ExifError::BadMarker(what) => write!(f, "Bad {}.", what),
+            ExifError::UnsupportedType(tag_type) => write!(f, "Unsupported tag type {}.", tag_type),
+            ExifError::UnsupportedFormat(file_type) => write!(f, "Reading exif from {} files isn't supported yet.", file_type),
+            ExifError::NoExif => write!(f, "Exif data either not present or adheres to some other format."),
+            ExifError::Unwritable(why) => write!(f, "Can't write this: {}.", why),
+            ExifError::SpecViolation(what) => write!(f, "Spec violation: {}.", what),
+            ExifError::Utf8(e) => write!(f, "Invalid utf8: {}", e),
+            ExifError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for ExifError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ExifError::Utf8(e) => Some(e),
+            ExifError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+// These let ? turn io and utf8 errors into ExifErrors for us.
+impl From<io::Error> for ExifError {
+    fn from(e: io::Error) -> Self {
+        ExifError::Io(e)
+    }
+}
+
+impl From<str::Utf8Error> for ExifError {
+    fn from(e: str::Utf8Error) -> Self {
+        ExifError::Utf8(e)
+    }
+}
@@ -0,0 +1,103 @@
+use std::{io, io::prelude::*};
+use std::fs::File;
+use std::fmt;
+use std::path::Path;
+
+// The kinds of file we can recognize from their first few bytes. File extensions are just part of
+// the name, and get changed (or lost) all the time, so rather than trusting "photo.jpg" we look at
+// the "magic" bytes that each format puts at the very start of a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileType {
+    Jpeg,
+    Png,
+    Tiff,
+    // Canon's raw format, a tiff with a few extra bytes of header. Other raw formats (Nikon's NEF,
+    // Sony's ARW) are plain tiffs as far as their magic goes, and come out as Tiff.
+    Cr2,
+    Heif,
+    Unknown,
+}
+
+impl FileType {
+    // Sniff the file type from the start of the buffer. Sources:
+    // JPEG: SOI marker ff d8, followed by the ff that starts the next marker.
+    // PNG: the 8 byte signature, https://www.w3.org/TR/png/#5PNG-file-signature
+    // TIFF: byte order (II or MM) followed by 42 in that byte order, or 43 for BigTIFF.
+    // CR2: a little endian TIFF header, then "CR" and a version, at byte 8.
+    // HEIF: an ISO-BMFF 'ftyp' box at byte 4, with one of the HEIF brands after it.
+    pub fn from_magic(buf: &[u8]) -> Self {
+        if buf.starts_with(&[0xff, 0xd8, 0xff]) {
+            FileType::Jpeg
+        } else if buf.starts_with(b"\x89PNG\r\n\x1a\n") {
+            FileType::Png
+        } else if buf.starts_with(b"II*\0") && buf.get(8..10) == Some(&b"CR"[..]) {
+            FileType::Cr2
+        } else if [&b"II*\0"[..], b"MM\0*", b"II+\0", b"MM\0+"].iter().any(|magic| buf.starts_with(magic)) {
+            FileType::Tiff
+        } else if buf.len() >= 12 && &buf[4..8] == b"ftyp" && is_heif_brand(&buf[8..12]) {
+            FileType::Heif
+        } else {
+            FileType::Unknown
+        }
+    }
+
+    // Sniff a file on disk. We only need the first 12 bytes for that, so there's no point reading
+    // the whole thing. take() stops us reading past those, and a file shorter than 12 bytes just
+    // gives us a shorter buffer, which from_magic copes with.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut magic = Vec::with_capacity(12);
+        File::open(path)?.take(12).read_to_end(&mut magic)?;
+        Ok(FileType::from_magic(&magic))
+    }
+
+    // Extensions people usually give files of this type, lowercase.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            FileType::Jpeg => &["jpg", "jpeg", "jpe", "jfif"],
+            FileType::Png => &["png"],
+            // Raw formats that don't have magic of their own look like this.
+            FileType::Tiff => &["tif", "tiff", "nef", "nrw", "arw", "srf", "sr2", "dng", "pef"],
+            FileType::Cr2 => &["cr2"],
+            FileType::Heif => &["heic", "heif", "hif"],
+            FileType::Unknown => &[],
+        }
+    }
+
+    // Does the extension on this path agree with what the bytes say? Unknown files and files
+    // without an extension get the benefit of the doubt, since there's nothing to compare.
+    pub fn matches_extension(&self, path: &Path) -> bool {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if *self != FileType::Unknown => {
+                self.extensions().contains(&ext.to_lowercase().as_str())
+            },
+            _ => true
+        }
+    }
+}
+
+impl FileType {
+    // Tiff, or built on it, so a tiff header at byte 0 and the whole file tiff structured.
+    pub fn is_tiff_based(&self) -> bool {
+        matches!(self, FileType::Tiff | FileType::Cr2)
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FileType::Jpeg => "JPEG",
+            FileType::Png => "PNG",
+            FileType::Tiff => "TIFF",
+            FileType::Cr2 => "CR2",
+            FileType::Heif => "HEIF",
+            FileType::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// HEIF files are ISO-BMFF files, and so are mp4s, so we have to check the "major brand" in the
+// ftyp box to tell them apart.
+fn is_heif_brand(brand: &[u8]) -> bool {
+    [&b"heic"[..], b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1"].contains(&brand)
+}
@@ -0,0 +1,406 @@
+use std::fmt;
+
+use crate::error::{ExifError, Result};
+use crate::ifd::IfdKind;
+use crate::tag::TagId;
+use crate::value::TagValue;
+use crate::Exif;
+
+// GeoTIFF: a handful of extra tags in IFD0 that say where on Earth an image is, for aerial and
+// satellite imagery and the like. Ref: http://docs.opengeospatial.org/is/19-008r4/19-008r4.html
+//
+// Two of them tie the pixels to the ground directly. ModelPixelScaleTag is how big a pixel is in
+// ground units (so, the resolution), and ModelTiepointTag pins raster points to model (map)
+// points, usually just the top left corner. The rest hold "GeoKeys", which say which coordinate
+// system those map points are in. Rather than a tag each, the keys are packed into one SHORT
+// directory tag (GeoKeyDirectoryTag), and anything that isn't a SHORT lives in one of two
+// parameter tags the keys point into, one for doubles and one for text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoTiff {
+    // Pixel size in X, Y and Z, in model units.
+    pub pixel_scale: Option<(f64, f64, f64)>,
+    pub tiepoints: Vec<Tiepoint>,
+    // A 4x4 affine matrix, row by row, for images that are rotated or sheared. Files have this
+    // instead of a scale and tiepoint, not as well.
+    pub transformation: Option<Vec<f64>>,
+    // KeyDirectoryVersion, KeyRevision and MinorRevision. Always 1, 1, and 0 or 1 in practice.
+    pub version: (u16, u16, u16),
+    pub keys: Vec<GeoKey>,
+}
+
+// A raster point (I, J, K) and the model point (X, Y, Z) it lands on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tiepoint {
+    pub raster: (f64, f64, f64),
+    pub model: (f64, f64, f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoKey {
+    pub id: u16,
+    pub value: GeoKeyValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoKeyValue {
+    Short(Vec<u16>),
+    Double(Vec<f64>),
+    Ascii(String),
+}
+
+// What kind of coordinates the model space is in, from GTModelTypeGeoKey.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelType {
+    Projected,
+    Geographic,
+    Geocentric,
+    Other(u16),
+}
+
+// Some key ids. Ref: GeoTIFF 1.1, section 7.
+const GT_MODEL_TYPE: u16 = 1024;
+const GT_CITATION: u16 = 1026;
+const GEOGRAPHIC_TYPE: u16 = 2048;
+const GEOG_CITATION: u16 = 2049;
+const PROJECTED_CS_TYPE: u16 = 3072;
+const PCS_CITATION: u16 = 3073;
+const PROJ_LINEAR_UNITS: u16 = 3076;
+
+// The code keys use for "not one of the standard ones, see the other keys for details".
+const USER_DEFINED: u16 = 32767;
+
+// Names for the keys we're likely to come across.
+const KEY_NAMES: &[(u16, &str)] = &[
+    (1024, "GTModelTypeGeoKey"),
+    (1025, "GTRasterTypeGeoKey"),
+    (1026, "GTCitationGeoKey"),
+    (2048, "GeographicTypeGeoKey"),
+    (2049, "GeogCitationGeoKey"),
+    (2050, "GeogGeodeticDatumGeoKey"),
+    (2051, "GeogPrimeMeridianGeoKey"),
+    (2052, "GeogLinearUnitsGeoKey"),
+    (2054, "GeogAngularUnitsGeoKey"),
+    (2056, "GeogEllipsoidGeoKey"),
+    (2057, "GeogSemiMajorAxisGeoKey"),
+    (2058, "GeogSemiMinorAxisGeoKey"),
+    (2059, "GeogInvFlatteningGeoKey"),
+    (3072, "ProjectedCSTypeGeoKey"),
+    (3073, "PCSCitationGeoKey"),
+    (3074, "ProjectionGeoKey"),
+    (3075, "ProjCoordTransGeoKey"),
+    (3076, "ProjLinearUnitsGeoKey"),
+    (4096, "VerticalCSTypeGeoKey"),
+    (4097, "VerticalCitationGeoKey"),
+    (4098, "VerticalDatumGeoKey"),
+    (4099, "VerticalUnitsGeoKey"),
+];
+
+impl GeoTiff {
+    // Read the GeoTIFF tags out of IFD0. None if the file doesn't have a key directory or any of
+    // the Model* tags, since then it isn't a GeoTIFF.
+    pub fn read(exif: &Exif) -> Result<Option<Self>> {
+        let value = |tag: TagId| -> Result<Option<TagValue>> {
+            match exif.entry_in(IfdKind::Ifd(0), tag) {
+                Some(entry) => exif.value(entry).map(Some),
+                None => Ok(None)
+            }
+        };
+        let pixel_scale = match value(TagId::ModelPixelScaleTag)? {
+            Some(value) => {
+                let v = doubles(value, "ModelPixelScaleTag")?;
+                if v.len() < 3 {
+                    return Err(ExifError::TruncatedBuffer("ModelPixelScaleTag"));
+                }
+                Some((v[0], v[1], v[2]))
+            },
+            None => None
+        };
+        let tiepoints = match value(TagId::ModelTiepointTag)? {
+            Some(value) => doubles(value, "ModelTiepointTag")?.chunks_exact(6).map(|t| Tiepoint {
+                raster: (t[0], t[1], t[2]),
+                model: (t[3], t[4], t[5]),
+            }).collect(),
+            None => Vec::new()
+        };
+        let transformation = match value(TagId::ModelTransformationTag)? {
+            Some(value) => Some(doubles(value, "ModelTransformationTag")?),
+            None => None
+        };
+        let directory = match value(TagId::GeoKeyDirectoryTag)? {
+            Some(TagValue::Short(v)) => Some(v),
+            Some(_) => return Err(ExifError::BadMarker("GeoKeyDirectoryTag type, it should be SHORT")),
+            None => None
+        };
+        if directory.is_none() && pixel_scale.is_none() && tiepoints.is_empty() && transformation.is_none() {
+            return Ok(None);
+        }
+        let double_params = match value(TagId::GeoDoubleParamsTag)? {
+            Some(value) => doubles(value, "GeoDoubleParamsTag")?,
+            None => Vec::new()
+        };
+        let ascii_params = match value(TagId::GeoAsciiParamsTag)? {
+            Some(TagValue::Ascii(s)) => s.into_owned(),
+            Some(_) => return Err(ExifError::BadMarker("GeoAsciiParamsTag type, it should be ASCII")),
+            None => String::new()
+        };
+
+        let (version, keys) = match directory {
+            Some(directory) => read_keys(&directory, &double_params, &ascii_params)?,
+            None => ((0, 0, 0), Vec::new())
+        };
+        Ok(Some(GeoTiff { pixel_scale, tiepoints, transformation, version, keys }))
+    }
+
+    // A key's value, if the file has it.
+    pub fn key(&self, id: u16) -> Option<&GeoKeyValue> {
+        self.keys.iter().find(|key| key.id == id).map(|key| &key.value)
+    }
+
+    // A key's value, if the file has it and it's a single SHORT, which most of them are.
+    fn short_key(&self, id: u16) -> Option<u16> {
+        match self.key(id) {
+            Some(GeoKeyValue::Short(v)) if v.len() == 1 => Some(v[0]),
+            _ => None
+        }
+    }
+
+    pub fn model_type(&self) -> Option<ModelType> {
+        self.short_key(GT_MODEL_TYPE).map(|code| match code {
+            1 => ModelType::Projected,
+            2 => ModelType::Geographic,
+            3 => ModelType::Geocentric,
+            code => ModelType::Other(code),
+        })
+    }
+
+    // The coordinate reference system, as best we can name it. The standard ones are EPSG codes,
+    // which is what everything else in GIS land calls them too. A projected system (map
+    // coordinates, like UTM) is more specific than the geographic one (latitude and longitude) it's
+    // built on, so we prefer that. Failing both, there may be a description in one of the
+    // citation keys.
+    pub fn crs(&self) -> Option<String> {
+        for id in [PROJECTED_CS_TYPE, GEOGRAPHIC_TYPE].iter() {
+            match self.short_key(*id) {
+                Some(code) if code != USER_DEFINED && code != 0 => return Some(format!("EPSG:{}", code)),
+                _ => {}
+            }
+        }
+        [PCS_CITATION, GEOG_CITATION, GT_CITATION].iter().find_map(|id| match self.key(*id) {
+            Some(GeoKeyValue::Ascii(s)) => Some(s.clone()),
+            _ => None
+        })
+    }
+
+    // Ground units, for projected systems, from ProjLinearUnitsGeoKey. Only the common ones.
+    pub fn linear_units(&self) -> Option<&'static str> {
+        match self.short_key(PROJ_LINEAR_UNITS)? {
+            9001 => Some("metre"),
+            9002 => Some("foot"),
+            9003 => Some("US survey foot"),
+            _ => None
+        }
+    }
+}
+
+// Unpack the key directory. It's SHORTs all the way down: a 4 SHORT header (three version numbers
+// and how many keys there are), then 4 SHORTs per key: its id, where its value is (0 for right
+// here, or the tag number of GeoDoubleParamsTag or GeoAsciiParamsTag, or the key directory itself),
+// how many values, and either the value itself or an index into wherever it is.
+fn read_keys(directory: &[u16], doubles: &[f64], ascii: &str) -> Result<((u16, u16, u16), Vec<GeoKey>)> {
+    if directory.len() < 4 {
+        return Err(ExifError::TruncatedBuffer("GeoKey directory header"));
+    }
+    let version = (directory[0], directory[1], directory[2]);
+    let count = directory[3] as usize;
+    let entries = directory[4..].chunks_exact(4).take(count);
+    if entries.len() < count {
+        return Err(ExifError::TruncatedBuffer("GeoKey directory"));
+    }
+    let mut keys = Vec::new();
+    for entry in entries {
+        let (id, location, n, offset) = (entry[0], entry[1], entry[2] as usize, entry[3] as usize);
+        let value = if location == 0 {
+            GeoKeyValue::Short(vec![entry[3]])
+        } else if location == TagId::GeoKeyDirectoryTag.id() {
+            GeoKeyValue::Short(directory.get(offset..offset + n).ok_or(ExifError::InvalidOffset("GeoKey"))?.to_vec())
+        } else if location == TagId::GeoDoubleParamsTag.id() {
+            GeoKeyValue::Double(doubles.get(offset..offset + n).ok_or(ExifError::InvalidOffset("GeoKey"))?.to_vec())
+        } else if location == TagId::GeoAsciiParamsTag.id() {
+            // Strings in here are separated by |s, and the count includes the |.
+            let s = ascii.get(offset..offset + n).ok_or(ExifError::InvalidOffset("GeoKey"))?;
+            GeoKeyValue::Ascii(s.trim_end_matches('|').to_string())
+        } else {
+            return Err(ExifError::BadMarker("GeoKey location"));
+        };
+        keys.push(GeoKey { id, value });
+    }
+    Ok((version, keys))
+}
+
+fn doubles(value: TagValue, what: &'static str) -> Result<Vec<f64>> {
+    match value {
+        TagValue::Double(v) => Ok(v),
+        _ => Err(ExifError::BadMarker(what))
+    }
+}
+
+impl GeoKey {
+    pub fn name(&self) -> Option<&'static str> {
+        KEY_NAMES.iter().find(|(id, _)| *id == self.id).map(|(_, name)| *name)
+    }
+}
+
+impl fmt::Display for GeoKeyValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |v: Vec<String>| v.join(", ");
+        match self {
+            GeoKeyValue::Short(v) => write!(f, "{}", join(v.iter().map(|v| v.to_string()).collect())),
+            GeoKeyValue::Double(v) => write!(f, "{}", join(v.iter().map(|v| v.to_string()).collect())),
+            GeoKeyValue::Ascii(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+// Keys display as name = value, or their number if we don't know their name.
+impl fmt::Display for GeoKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} = {}", name, self.value),
+            None => write!(f, "GeoKey({}) = {}", self.id, self.value)
+        }
+    }
+}
+
+impl fmt::Display for ModelType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelType::Projected => write!(f, "projected"),
+            ModelType::Geographic => write!(f, "geographic (latitude, longitude)"),
+            ModelType::Geocentric => write!(f, "geocentric"),
+            ModelType::Other(code) => write!(f, "unknown ({})", code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::ParseMode;
+
+    // A little endian tiff with nothing but these entries in IFD0, each (tag, type, count, value).
+    fn tiff(entries: &[(TagId, u16, usize, Vec<u8>)]) -> Vec<u8> {
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        let mut data = Vec::new();
+        let data_start = 8 + 2 + 12 * entries.len() + 4;
+        tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, tag_type, count, value) in entries {
+            tiff.extend_from_slice(&tag.id().to_le_bytes());
+            tiff.extend_from_slice(&tag_type.to_le_bytes());
+            tiff.extend_from_slice(&(*count as u32).to_le_bytes());
+            if value.len() <= 4 {
+                tiff.extend_from_slice(&[value.as_slice(), &[0; 4][value.len()..]].concat());
+            } else {
+                tiff.extend_from_slice(&((data_start + data.len()) as u32).to_le_bytes());
+                data.extend_from_slice(value);
+            }
+        }
+        tiff.extend_from_slice(&[0; 4]);
+        tiff.extend_from_slice(&data);
+        tiff
+    }
+
+    fn double_values(v: &[f64]) -> (u16, usize, Vec<u8>) {
+        (12, v.len(), v.iter().flat_map(|v| v.to_le_bytes()).collect())
+    }
+
+    fn short_values(v: &[u16]) -> (u16, usize, Vec<u8>) {
+        (3, v.len(), v.iter().flat_map(|v| v.to_le_bytes()).collect())
+    }
+
+    fn entry(tag: TagId, (tag_type, count, value): (u16, usize, Vec<u8>)) -> (TagId, u16, usize, Vec<u8>) {
+        (tag, tag_type, count, value)
+    }
+
+    const ASCII_PARAMS: &str = "WGS 84 / UTM zone 33N|NAD27|";
+
+    // A UTM image, 0.5m pixels, with its top left corner at 500000E 4649776N.
+    fn utm() -> Vec<u8> {
+        let directory = [
+            1, 1, 0, 6,
+            GT_MODEL_TYPE, 0, 1, 1,
+            GT_CITATION, TagId::GeoAsciiParamsTag.id(), 22, 0,
+            2052, TagId::GeoKeyDirectoryTag.id(), 2, 28,
+            2057, TagId::GeoDoubleParamsTag.id(), 1, 0,
+            PROJECTED_CS_TYPE, 0, 1, 32633,
+            PCS_CITATION, TagId::GeoAsciiParamsTag.id(), 6, 22,
+            9001, 9002,
+        ];
+        tiff(&[
+            entry(TagId::ModelPixelScaleTag, double_values(&[0.5, 0.5, 0.0])),
+            entry(TagId::ModelTiepointTag, double_values(&[0.0, 0.0, 0.0, 500000.0, 4649776.0, 0.0])),
+            entry(TagId::GeoKeyDirectoryTag, short_values(&directory)),
+            entry(TagId::GeoDoubleParamsTag, double_values(&[6378137.0])),
+            (TagId::GeoAsciiParamsTag, 2, ASCII_PARAMS.len() + 1, format!("{}\0", ASCII_PARAMS).into_bytes()),
+        ])
+    }
+
+    fn read(buf: &[u8]) -> Result<Option<GeoTiff>> {
+        GeoTiff::read(&Exif::from_bytes_with(buf, ParseMode::Strict)?)
+    }
+
+    #[test]
+    fn decodes_a_key_directory() {
+        let geotiff = read(&utm()).unwrap().unwrap();
+        assert_eq!(geotiff.version, (1, 1, 0));
+        assert_eq!(geotiff.keys.len(), 6);
+        assert_eq!(geotiff.model_type(), Some(ModelType::Projected));
+        assert_eq!(geotiff.key(GT_CITATION), Some(&GeoKeyValue::Ascii("WGS 84 / UTM zone 33N".to_string())));
+        assert_eq!(geotiff.key(PCS_CITATION), Some(&GeoKeyValue::Ascii("NAD27".to_string())));
+        assert_eq!(geotiff.key(2052), Some(&GeoKeyValue::Short(vec![9001, 9002])));
+        assert_eq!(geotiff.key(2057), Some(&GeoKeyValue::Double(vec![6378137.0])));
+        assert_eq!(geotiff.key(4096), None);
+        assert_eq!(geotiff.keys[2].to_string(), "GeogLinearUnitsGeoKey = 9001, 9002");
+    }
+
+    #[test]
+    fn reads_the_scale_and_tiepoints() {
+        let geotiff = read(&utm()).unwrap().unwrap();
+        assert_eq!(geotiff.pixel_scale, Some((0.5, 0.5, 0.0)));
+        assert_eq!(geotiff.tiepoints, [Tiepoint { raster: (0.0, 0.0, 0.0), model: (500000.0, 4649776.0, 0.0) }]);
+        assert_eq!(geotiff.transformation, None);
+    }
+
+    #[test]
+    fn crs_codes() {
+        let mut geotiff = read(&utm()).unwrap().unwrap();
+        assert_eq!(geotiff.crs().as_deref(), Some("EPSG:32633"));
+        assert_eq!(geotiff.linear_units(), None);
+
+        // A user defined projection falls back to its citation, and then to the geographic system.
+        geotiff.keys[4].value = GeoKeyValue::Short(vec![USER_DEFINED]);
+        assert_eq!(geotiff.crs().as_deref(), Some("NAD27"));
+        geotiff.keys.push(GeoKey { id: GEOGRAPHIC_TYPE, value: GeoKeyValue::Short(vec![4326]) });
+        geotiff.keys.push(GeoKey { id: PROJ_LINEAR_UNITS, value: GeoKeyValue::Short(vec![9001]) });
+        assert_eq!(geotiff.crs().as_deref(), Some("EPSG:4326"));
+        assert_eq!(geotiff.linear_units(), Some("metre"));
+    }
+
+    #[test]
+    fn not_a_geotiff() {
+        let buf = tiff(&[(TagId::Make, 2, 4, b"Foo\0".to_vec())]);
+        assert_eq!(read(&buf).unwrap(), None);
+    }
+
+    #[test]
+    fn bad_key_directories() {
+        // Fewer keys than it says, and keys pointing past the end of their values.
+        assert!(matches!(read_keys(&[1, 1, 0, 2, GT_MODEL_TYPE, 0, 1, 1], &[], ""), Err(ExifError::TruncatedBuffer(_))));
+        assert!(matches!(read_keys(&[1, 1, 0], &[], ""), Err(ExifError::TruncatedBuffer(_))));
+        let directory = [1, 1, 0, 1, 2057, TagId::GeoDoubleParamsTag.id(), 2, 0];
+        assert!(matches!(read_keys(&directory, &[1.0], ""), Err(ExifError::InvalidOffset(_))));
+        let directory = [1, 1, 0, 1, GT_CITATION, TagId::GeoAsciiParamsTag.id(), 4, u16::MAX];
+        assert!(matches!(read_keys(&directory, &[], "abc|"), Err(ExifError::InvalidOffset(_))));
+        let directory = [1, 1, 0, 1, GT_CITATION, TagId::Make.id(), 1, 0];
+        assert!(matches!(read_keys(&directory, &[], ""), Err(ExifError::BadMarker(_))));
+    }
+}
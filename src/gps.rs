@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::error::Result;
+use crate::ifd::IfdKind;
+use crate::tag::TagId;
+use crate::value::TagValue;
+use crate::Exif;
+
+// Where (and when, by the satellites' clock) a photo was taken, from the GPS IFD, in the form
+// everything else wants it. The GPS IFD stores latitude and longitude as three rationals (degrees,
+// minutes, seconds), always positive, with a separate N/S or E/W tag saying which side of the
+// equator or meridian, and altitude the same way, with a reference tag for above or below sea
+// level. Ref: https://www.exif.org/Exif2-2.PDF, 4.6.6.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GpsInfo {
+    // Decimal degrees, negative south of the equator.
+    pub latitude: Option<f64>,
+    // Decimal degrees, negative west of Greenwich.
+    pub longitude: Option<f64>,
+    // Metres, negative below sea level.
+    pub altitude: Option<f64>,
+    // UTC, as ISO 8601: "2008-05-30T15:56:01Z", or just the time, "15:56:01Z", if there's no
+    // GPSDateStamp to go with GPSTimeStamp.
+    pub timestamp: Option<String>,
+}
+
+impl GpsInfo {
+    // Read what there is of it. Ok(None) if there's no GPS IFD at all. Coordinates that can't be
+    // made sense of (a missing reference, a zero denominator) come out as None, rather than as a
+    // wrong number.
+    pub fn read(exif: &Exif) -> Result<Option<Self>> {
+        if exif.gps_ifd().is_none() {
+            return Ok(None);
+        }
+        let value = |tag: TagId| -> Result<Option<TagValue>> {
+            match exif.entry_in(IfdKind::Gps, tag) {
+                Some(entry) => exif.value(entry).map(Some),
+                None => Ok(None)
+            }
+        };
+        let latitude = coordinate(value(TagId::GPSLatitude)?, value(TagId::GPSLatitudeRef)?, 'N', 'S');
+        let longitude = coordinate(value(TagId::GPSLongitude)?, value(TagId::GPSLongitudeRef)?, 'E', 'W');
+
+        // No reference means above sea level, which is the default the spec gives.
+        let altitude = value(TagId::GPSAltitude)?.and_then(|v| rationals(&v)?.first().copied()).map(|metres| {
+            match value(TagId::GPSAltitudeRef) {
+                Ok(Some(TagValue::Byte(r))) if r.first() == Some(&1) => -metres,
+                _ => metres
+            }
+        });
+
+        let time = value(TagId::GPSTimeStamp)?.and_then(|v| rationals(&v)).filter(|t| t.len() == 3);
+        let date = match value(TagId::GPSDateStamp)? {
+            Some(TagValue::Ascii(s)) => Some(s.trim().replace(':', "-")),
+            _ => None
+        };
+        let timestamp = time.map(|t| {
+            let time = format!("{}Z", time_of_day(t[0], t[1], t[2]));
+            match &date {
+                Some(date) => format!("{}T{}", date, time),
+                None => time
+            }
+        });
+
+        Ok(Some(GpsInfo { latitude, longitude, altitude, timestamp }))
+    }
+}
+
+// A rational (or list of them) as floats. None for anything that isn't a rational, or has a zero
+// denominator somewhere, since some cameras write 0/0 for "don't know".
+fn rationals(value: &TagValue) -> Option<Vec<f64>> {
+    match value {
+        TagValue::Rational(v) => v.iter().map(|r| r.to_f64()).collect(),
+        TagValue::SRational(v) => v.iter().map(|r| r.to_f64()).collect(),
+        _ => None
+    }
+}
+
+// Degrees, minutes and seconds, and which way, as signed decimal degrees. Some writers put all the
+// precision in the degrees or minutes and leave the rest 0, which works out the same.
+fn coordinate(dms: Option<TagValue>, reference: Option<TagValue>, positive: char, negative: char) -> Option<f64> {
+    let dms = rationals(&dms?)?;
+    if dms.len() != 3 {
+        return None;
+    }
+    let degrees = dms[0] + dms[1] / 60.0 + dms[2] / 3600.0;
+    match reference? {
+        TagValue::Ascii(r) => match r.trim().chars().next()?.to_ascii_uppercase() {
+            c if c == positive => Some(degrees),
+            c if c == negative => Some(-degrees),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// GPSTimeStamp's hours, minutes and seconds as hh:mm:ss, with as many decimal places as the
+// seconds need, up to 3. Rounding to the millisecond can carry: 59.9996 seconds is the next minute,
+// and 23:59:59.9996 is 24:00:00, which ISO 8601 allows for the end of a day.
+fn time_of_day(hours: f64, minutes: f64, seconds: f64) -> String {
+    // as saturates, so nonsense (negative, huge, NaN) can't overflow anything.
+    let millis = ((hours.trunc() * 3600.0 + minutes.trunc() * 60.0) * 1000.0 + (seconds * 1000.0).round()) as u64;
+    let time = format!("{:02}:{:02}:{:02}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60);
+    match millis % 1000 {
+        0 => time,
+        fraction => format!("{}.{}", time, format!("{:03}", fraction).trim_end_matches('0')),
+    }
+}
+
+impl fmt::Display for GpsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            parts.push(format!("{:.6}, {:.6}", latitude, longitude));
+        }
+        if let Some(altitude) = self.altitude {
+            parts.push(format!("{} m", altitude));
+        }
+        if let Some(timestamp) = &self.timestamp {
+            parts.push(timestamp.clone());
+        }
+        if parts.is_empty() {
+            write!(f, "no position")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_times_of_day() {
+        assert_eq!(time_of_day(15.0, 56.0, 1.0), "15:56:01");
+        assert_eq!(time_of_day(15.0, 56.0, 1.5), "15:56:01.5");
+        assert_eq!(time_of_day(15.0, 56.0, 1.25), "15:56:01.25");
+    }
+
+    #[test]
+    fn rounds_seconds_without_leaving_a_point() {
+        assert_eq!(time_of_day(15.0, 56.0, 12.0001), "15:56:12");
+        assert_eq!(time_of_day(15.0, 56.0, 12.9999), "15:56:13");
+    }
+
+    #[test]
+    fn carries_rounding_into_minutes_and_hours() {
+        assert_eq!(time_of_day(15.0, 56.0, 59.9996), "15:57:00");
+        assert_eq!(time_of_day(15.0, 59.0, 59.9996), "16:00:00");
+        assert_eq!(time_of_day(23.0, 59.0, 59.9996), "24:00:00");
+    }
+
+    #[test]
+    fn copes_with_nonsense() {
+        assert_eq!(time_of_day(-1.0, f64::NAN, -5.0), "00:00:00");
+        time_of_day(f64::MAX, f64::MAX, f64::INFINITY);
+    }
+}
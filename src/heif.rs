@@ -0,0 +1,283 @@
+use std::ops::Range;
+
+use crate::error::{ExifError, Result};
+use crate::tiff::{slice_at, to_usize, ByteOrder};
+
+// HEIF (and HEIC, which is HEIF with HEVC compressed images, and what iPhones take) is built out of
+// ISO base media file format boxes, the same as mp4. Every box starts with a 4 byte big endian
+// size, counting the header, and a 4 letter type. A size of 1 means the real size is in the 8
+// bytes after the type, and a size of 0 means the box runs to the end of the file. Some boxes just
+// hold other boxes, and "full" boxes put a version byte and 3 bytes of flags in front of those.
+// Ref: ISO/IEC 14496-12 (ISOBMFF) section 4.2, and ISO/IEC 23008-12 (HEIF).
+//
+// Exif isn't a box of its own. Everything in a HEIF is an "item" (the images, the thumbnails, the
+// exif), listed in the meta box. Its iinf box says what type each item is, and its iloc box says
+// where each item's bytes are. So we look up the id of the item whose type is "Exif" in one, and
+// then where that id's bytes are in the other.
+const BE: ByteOrder = ByteOrder::BigEndian;
+
+struct BmffBox {
+    box_type: [u8; 4],
+    // Where the box's contents are, after its header.
+    content: Range<usize>,
+}
+
+// Every box in buf[range], in order.
+fn read_boxes(buf: &[u8], range: Range<usize>) -> Result<Vec<BmffBox>> {
+    let mut boxes = Vec::new();
+    let mut pos = range.start;
+    while pos < range.end {
+        let size = BE.u32_at(buf, pos, "HEIF box size")? as u64;
+        let header = slice_at(buf, pos + 4, 4).ok_or(ExifError::TruncatedBuffer("HEIF box type"))?;
+        let box_type = [header[0], header[1], header[2], header[3]];
+        let (header_size, size) = match size {
+            0 => (8, (range.end - pos) as u64),
+            1 => (16, BE.u64_at(buf, pos + 8, "HEIF box size")?),
+            size => (8, size),
+        };
+        let end = to_usize(size, "HEIF box").ok()
+            .and_then(|size| pos.checked_add(size))
+            .filter(|&end| end <= range.end && end >= pos + header_size)
+            .ok_or(ExifError::InvalidOffset("HEIF box"))?;
+        boxes.push(BmffBox { box_type, content: pos + header_size..end });
+        pos = end;
+    }
+    Ok(boxes)
+}
+
+fn find_box<'a>(boxes: &'a [BmffBox], box_type: &[u8]) -> Option<&'a BmffBox> {
+    boxes.iter().find(|b| b.box_type == box_type)
+}
+
+// A number of `size` bytes (0, 4 or 8, as iloc allows) at *pos, moving pos past it.
+fn read_sized(buf: &[u8], pos: &mut usize, size: usize) -> Result<u64> {
+    let value = match size {
+        0 => 0,
+        4 => BE.u32_at(buf, *pos, "HEIF iloc")? as u64,
+        8 => BE.u64_at(buf, *pos, "HEIF iloc")?,
+        _ => return Err(ExifError::BadMarker("HEIF iloc field size")),
+    };
+    *pos += size;
+    Ok(value)
+}
+
+fn read_u16(buf: &[u8], pos: &mut usize) -> Result<u16> {
+    let value = BE.u16_at(buf, *pos, "HEIF box")?;
+    *pos += 2;
+    Ok(value)
+}
+
+fn read_u32(buf: &[u8], pos: &mut usize) -> Result<u32> {
+    let value = BE.u32_at(buf, *pos, "HEIF box")?;
+    *pos += 4;
+    Ok(value)
+}
+
+// A full box's version, and where its contents start after the version and flags.
+fn full_box(buf: &[u8], b: &BmffBox) -> Result<(u8, usize)> {
+    let version = *buf.get(b.content.start).ok_or(ExifError::TruncatedBuffer("HEIF box version"))?;
+    Ok((version, b.content.start + 4))
+}
+
+// Find the exif item in a HEIF, and return where its tiff data starts and ends, like
+// find_exif_segment does for jpegs. None if the file doesn't have an exif item.
+pub fn find_exif_item(buf: &[u8]) -> Result<Option<Range<usize>>> {
+    let top = read_boxes(buf, 0..buf.len())?;
+    let meta = find_box(&top, b"meta").ok_or(ExifError::BadMarker("HEIF, there's no meta box"))?;
+    let (_, meta_start) = full_box(buf, meta)?;
+    let children = read_boxes(buf, meta_start..meta.content.end)?;
+
+    let id = match exif_item_id(buf, &children)? {
+        Some(id) => id,
+        None => return Ok(None)
+    };
+    let iloc = find_box(&children, b"iloc").ok_or(ExifError::BadMarker("HEIF, there's no iloc box"))?;
+    let item = item_location(buf, iloc, id)?;
+    // Items stored in the meta box's idat, rather than the file at large, are offsets into that,
+    // and have to fit inside it.
+    let item = match item.construction_method {
+        0 => item.range,
+        1 => {
+            let idat = find_box(&children, b"idat").ok_or(ExifError::BadMarker("HEIF, there's no idat box"))?;
+            let start = idat.content.start.checked_add(item.range.start);
+            let end = start.and_then(|start| start.checked_add(item.range.len()));
+            match (start, end) {
+                (Some(start), Some(end)) if end <= idat.content.end => start..end,
+                _ => return Err(ExifError::InvalidOffset("HEIF exif item, it's outside the idat box"))
+            }
+        },
+        _ => return Err(ExifError::BadMarker("HEIF exif item construction method")),
+    };
+    if item.end > buf.len() {
+        return Err(ExifError::InvalidOffset("HEIF exif item"));
+    }
+
+    // The item starts with a 4 byte offset from the end of itself to the tiff header, which
+    // usually skips over an "Exif\0\0" like the one in a jpeg.
+    let skip = BE.u32_at(buf, item.start, "HEIF exif header offset")? as usize;
+    let tiff_start = (item.start + 4).checked_add(skip).filter(|&start| start <= item.end)
+        .ok_or(ExifError::InvalidOffset("HEIF exif header"))?;
+    Ok(Some(tiff_start..item.end))
+}
+
+// Look through the iinf box's infe (item info entry) boxes for the one with type "Exif". Only
+// versions 2 and 3 of infe have types; earlier ones predate HEIF.
+fn exif_item_id(buf: &[u8], meta: &[BmffBox]) -> Result<Option<u32>> {
+    let iinf = find_box(meta, b"iinf").ok_or(ExifError::BadMarker("HEIF, there's no iinf box"))?;
+    let (version, mut pos) = full_box(buf, iinf)?;
+    // The entry count, which we don't need, since the boxes themselves say how many there are.
+    pos += if version == 0 { 2 } else { 4 };
+    for infe in read_boxes(buf, pos..iinf.content.end)?.iter().filter(|b| &b.box_type == b"infe") {
+        let (version, mut pos) = full_box(buf, infe)?;
+        let id = match version {
+            2 => read_u16(buf, &mut pos)? as u32,
+            3 => read_u32(buf, &mut pos)?,
+            _ => continue
+        };
+        // Skip item_protection_index.
+        pos += 2;
+        if slice_at(buf, pos, 4) == Some(&b"Exif"[..]) {
+            return Ok(Some(id));
+        }
+    }
+    Ok(None)
+}
+
+struct ItemLocation {
+    construction_method: u16,
+    range: Range<usize>,
+}
+
+// Find an item in the iloc box. Its layout depends on its version, and the sizes of most of its
+// fields are given up front, in nibbles:
+//
+//   offset_size, length_size, base_offset_size, and index_size (or reserved, in version 0)
+//   item count, 2 bytes (4 in version 2)
+//   for each item:
+//     item id, 2 bytes (4 in version 2)
+//     construction method, 2 bytes (versions 1 and 2 only)
+//     data reference index, 2 bytes
+//     base offset
+//     extent count, 2 bytes
+//     for each extent: extent index (versions 1 and 2), extent offset, extent length
+//
+// The item's bytes are its extents, one after another, each at base offset + extent offset. Exif
+// is small, and always one extent in practice, which is all we handle.
+fn item_location(buf: &[u8], iloc: &BmffBox, wanted: u32) -> Result<ItemLocation> {
+    let (version, mut pos) = full_box(buf, iloc)?;
+    let sizes = read_u16(buf, &mut pos)?;
+    let offset_size = (sizes >> 12) as usize;
+    let length_size = (sizes >> 8 & 0xf) as usize;
+    let base_offset_size = (sizes >> 4 & 0xf) as usize;
+    let index_size = if version > 0 { (sizes & 0xf) as usize } else { 0 };
+    let count = if version < 2 { read_u16(buf, &mut pos)? as u32 } else { read_u32(buf, &mut pos)? };
+    for _ in 0..count {
+        let id = if version < 2 { read_u16(buf, &mut pos)? as u32 } else { read_u32(buf, &mut pos)? };
+        let construction_method = if version > 0 { read_u16(buf, &mut pos)? & 0xf } else { 0 };
+        // Data reference index. 0 means "this file", and we don't follow references elsewhere.
+        read_u16(buf, &mut pos)?;
+        let base_offset = read_sized(buf, &mut pos, base_offset_size)?;
+        let extent_count = read_u16(buf, &mut pos)?;
+        let mut extents = Vec::new();
+        for _ in 0..extent_count {
+            read_sized(buf, &mut pos, index_size)?;
+            let offset = read_sized(buf, &mut pos, offset_size)?;
+            let length = read_sized(buf, &mut pos, length_size)?;
+            extents.push((offset, length));
+        }
+        if id != wanted {
+            continue;
+        }
+        if extents.len() != 1 {
+            return Err(ExifError::BadMarker("HEIF exif item, it isn't in one piece"));
+        }
+        let (offset, length) = extents[0];
+        let start = base_offset.checked_add(offset).ok_or(ExifError::InvalidOffset("HEIF exif item"))?;
+        let end = start.checked_add(length).ok_or(ExifError::InvalidOffset("HEIF exif item"))?;
+        return Ok(ItemLocation {
+            construction_method,
+            range: to_usize(start, "HEIF exif item")?..to_usize(end, "HEIF exif item")?,
+        });
+    }
+    Err(ExifError::BadMarker("HEIF, the exif item isn't in iloc"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXIF_HEIC: &[u8] = include_bytes!("../fixtures/exif.heic");
+
+    fn bmff(box_type: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut b = ((8 + content.len()) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(box_type);
+        b.extend_from_slice(content);
+        b
+    }
+
+    fn full(box_type: &[u8], version: u8, content: &[u8]) -> Vec<u8> {
+        bmff(box_type, &[&[version, 0, 0, 0], content].concat())
+    }
+
+    // A HEIF whose exif item, id 1, is a version 1 iloc's extents, in an idat box, followed by an
+    // mdat with some more bytes in it.
+    fn heif_with_idat(extents: &[(u32, u32)], idat: &[u8]) -> Vec<u8> {
+        let infe = full(b"infe", 2, b"\0\x01\0\0Exif\0");
+        let iinf = full(b"iinf", 0, &[&[0, 1][..], &infe].concat());
+        // 4 byte offsets and lengths, no base offset or index. One item, id 1, construction
+        // method 1, data reference 0.
+        let mut iloc = vec![0x44, 0x00, 0, 1, 0, 1, 0, 1, 0, 0, 0, extents.len() as u8];
+        for (offset, length) in extents {
+            iloc.extend_from_slice(&offset.to_be_bytes());
+            iloc.extend_from_slice(&length.to_be_bytes());
+        }
+        let meta = full(b"meta", 0, &[full(b"iloc", 1, &iloc), iinf, bmff(b"idat", idat)].concat());
+        [bmff(b"ftyp", b"heic\0\0\0\0mif1heic"), meta, bmff(b"mdat", &[0; 64])].concat()
+    }
+
+    fn exif_item() -> Vec<u8> {
+        let tiff = &EXIF_HEIC[0x98..];
+        [&6u32.to_be_bytes()[..], b"Exif\0\0", tiff].concat()
+    }
+
+    #[test]
+    fn finds_the_exif_item() {
+        let range = find_exif_item(EXIF_HEIC).unwrap().unwrap();
+        assert_eq!(range, 0x98..EXIF_HEIC.len());
+        assert!(EXIF_HEIC[range].starts_with(b"II*\0"));
+    }
+
+    #[test]
+    fn finds_the_exif_item_in_idat() {
+        let item = exif_item();
+        let idat = [&b"padding!"[..], &item].concat();
+        let heif = heif_with_idat(&[(8, item.len() as u32)], &idat);
+        let range = find_exif_item(&heif).unwrap().unwrap();
+        assert_eq!(&heif[range], &item[10..]);
+    }
+
+    #[test]
+    fn idat_items_have_to_fit_in_idat() {
+        let item = exif_item();
+        // Running past the end of idat, into the mdat after it, is still inside the file.
+        let heif = heif_with_idat(&[(0, item.len() as u32 + 8)], &item);
+        assert!(matches!(find_exif_item(&heif), Err(ExifError::InvalidOffset(_))));
+        let heif = heif_with_idat(&[(u32::MAX, 16)], &item);
+        assert!(matches!(find_exif_item(&heif), Err(ExifError::InvalidOffset(_))));
+    }
+
+    #[test]
+    fn refuses_items_in_more_than_one_piece() {
+        let item = exif_item();
+        let heif = heif_with_idat(&[(0, 10), (10, item.len() as u32 - 10)], &item);
+        assert!(matches!(find_exif_item(&heif), Err(ExifError::BadMarker(_))));
+    }
+
+    #[test]
+    fn no_exif_item() {
+        let mut heif = EXIF_HEIC.to_vec();
+        let at = heif.windows(4).position(|bytes| bytes == b"Exif").unwrap();
+        heif[at..at + 4].copy_from_slice(b"hvc1");
+        assert_eq!(find_exif_item(&heif).unwrap(), None);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::filetype::FileType;
+use crate::jpeg::{header_segments, DQT};
+use crate::tag::TagId;
+use crate::value::TagValue;
+use crate::Exif;
+
+// Guessing where a file came from when its Make and Model tags have been taken out (or were never
+// there). Cameras leave fingerprints all over their files besides those two tags: the signature
+// at the start of their maker notes, the quantization tables they compress with, the software that
+// last saved them. None of these is proof on its own, which is why we give our reasons, and why
+// there can be more than one guess.
+//
+// The database is small, and only grows from files we've actually seen. Quantization tables in
+// particular depend on the camera's quality setting (and sometimes the scene), so a camera we
+// know can still go unrecognised.
+
+// Something a file has that points at where it came from.
+pub struct Clue {
+    // The camera, phone or program we think it points at.
+    pub source: String,
+    // Why.
+    pub reason: String,
+}
+
+// Maker notes start with a signature naming the manufacturer, for most manufacturers. Canon's
+// don't, they go straight into a directory. Ref: https://exiftool.org/makernote_types.html
+const MAKER_NOTE_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"Nikon\0", "Nikon"),
+    (b"OLYMPUS\0", "Olympus"),
+    (b"OLYMP\0", "Olympus"),
+    (b"OM SYSTEM\0", "OM System"),
+    (b"FUJIFILM", "Fujifilm"),
+    (b"Panasonic\0", "Panasonic"),
+    (b"SONY DSC \0", "Sony"),
+    (b"SONY CAM \0", "Sony"),
+    (b"SONY MOBILE\0", "Sony"),
+    (b"AOC\0", "Pentax"),
+    (b"PENTAX \0", "Pentax"),
+    (b"Apple iOS\0", "Apple iPhone or iPad"),
+    (b"LEICA", "Leica"),
+    (b"SIGMA\0", "Sigma"),
+    (b"Ricoh", "Ricoh"),
+    (b"RICOH", "Ricoh"),
+    (b"KDK", "Kodak"),
+    (b"QVC\0", "Casio"),
+    (b"MLT0", "Minolta"),
+    (b"HUAWEI\0", "Huawei"),
+];
+
+// The quantization tables (every DQT segment before the first scan, hashed with fnv1a) of jpegs
+// straight from these cameras. See quantization_hash. Only files nothing has re-saved since are
+// any use here: the Canon and Kodak samples in the repository went through GIMP, so their tables
+// are libjpeg's, not the cameras'.
+const QUANTIZATION_TABLES: &[(u64, &str)] = &[
+    (0xd7ba_79d8_9d7a_0604, "Olympus \u{b5}1020"),
+];
+
+// What the Software tag starts with, for programs that put their name there. These are what saved
+// the file last, not what took it, but that's worth knowing too.
+const SOFTWARE: &[(&str, &str)] = &[
+    ("Adobe Photoshop Lightroom", "Adobe Lightroom"),
+    ("Adobe Photoshop", "Adobe Photoshop"),
+    ("GIMP", "GIMP"),
+    ("darktable", "darktable"),
+    ("RawTherapee", "RawTherapee"),
+    ("Picasa", "Picasa"),
+    ("Instagram", "Instagram"),
+];
+
+// The example luminance table from the JPEG spec, in the zigzag order DQT stores it in. libjpeg
+// scales this for its quality setting, and so do most programs built on it, so a match means the
+// file was saved by software, rather than coming straight from a camera, which have their own.
+// Ref: https://www.w3.org/Graphics/JPEG/itu-t81.pdf, Table K.1
+const STANDARD_LUMINANCE: [u16; 64] = [
+    16, 11, 12, 14, 12, 10, 16, 14, 13, 14, 18, 17, 16, 19, 24, 40,
+    26, 24, 22, 22, 24, 49, 35, 37, 29, 40, 58, 51, 61, 60, 57, 51,
+    56, 55, 64, 72, 92, 78, 64, 68, 87, 69, 55, 56, 80, 109, 81, 87,
+    95, 98, 103, 104, 103, 62, 77, 113, 121, 112, 100, 120, 92, 101, 103, 99,
+];
+
+// Everything we can find in buf that says where it came from.
+pub fn identify(buf: &[u8]) -> Vec<Clue> {
+    let mut clues = Vec::new();
+    if let Ok(exif) = Exif::from_bytes(buf) {
+        exif_clues(&exif, &mut clues);
+    }
+    if FileType::from_magic(buf) == FileType::Jpeg {
+        jpeg_clues(buf, &mut clues);
+    }
+    clues
+}
+
+fn exif_clues(exif: &Exif, clues: &mut Vec<Clue>) {
+    if let Some(Ok(TagValue::Undefined(maker_note))) = exif.entry(TagId::MakerNote).map(|entry| exif.value(entry)) {
+        if let Some((signature, maker)) = MAKER_NOTE_SIGNATURES.iter().find(|(signature, _)| maker_note.starts_with(signature)) {
+            let signature = String::from_utf8_lossy(signature.strip_suffix(b"\0").unwrap_or(signature)).trim().to_string();
+            clues.push(Clue { source: maker.to_string(), reason: format!("its maker note starts with {:?}", signature) });
+        }
+    }
+    if let Some(Ok(TagValue::Ascii(software))) = exif.entry(TagId::Software).map(|entry| exif.value(entry)) {
+        if let Some((_, program)) = SOFTWARE.iter().find(|(prefix, _)| software.starts_with(prefix)) {
+            clues.push(Clue { source: program.to_string(), reason: format!("the Software tag says {:?} (this is what last saved it)", software.trim()) });
+        }
+    }
+}
+
+fn jpeg_clues(buf: &[u8], clues: &mut Vec<Clue>) {
+    let tables = match quantization_tables(buf) {
+        Some(tables) if !tables.is_empty() => tables,
+        _ => return
+    };
+    let hash = quantization_hash(&tables);
+    if let Some((_, camera)) = QUANTIZATION_TABLES.iter().find(|(known, _)| *known == hash) {
+        clues.push(Clue { source: camera.to_string(), reason: "its quantization tables match ones we've seen from that camera".to_string() });
+    }
+    if let Some(quality) = tables.iter().find(|(id, _)| *id == 0).and_then(|(_, table)| libjpeg_quality(table)) {
+        clues.push(Clue { source: "libjpeg, or software built on it".to_string(), reason: format!("its quantization tables are libjpeg's, at quality {}", quality) });
+    }
+}
+
+// The quantization tables defined before the first scan, as their ids and values, in the zigzag
+// order they're stored in. None if the segments don't make sense. Ref: itu-t81.pdf, B.2.4.1
+fn quantization_tables(buf: &[u8]) -> Option<Vec<(u8, Vec<u16>)>> {
+    let (segments, _) = header_segments(buf).ok()?;
+    let mut tables = Vec::new();
+    for (_, range) in segments.into_iter().filter(|(marker, _)| *marker == DQT) {
+        // Past the marker and length, a DQT segment is one or more tables, each a byte of
+        // precision (8 or 16 bit values) and id, then 64 values.
+        let mut data = &buf[range.start + 4..range.end];
+        while let Some((&info, rest)) = data.split_first() {
+            let (id, sixteen_bit) = (info & 0x0f, info >> 4 == 1);
+            let size = if sixteen_bit { 128 } else { 64 };
+            let values = rest.get(..size)?;
+            let values = if sixteen_bit {
+                values.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect()
+            } else {
+                values.iter().map(|&value| value as u16).collect()
+            };
+            tables.push((id, values));
+            data = &rest[size..];
+        }
+    }
+    Some(tables)
+}
+
+// A 64 bit FNV-1a hash of the tables, ids included, in the order they're defined, for looking up
+// in QUANTIZATION_TABLES. Ref: http://www.isthe.com/chongo/tech/comp/fnv/
+fn quantization_hash(tables: &[(u8, Vec<u16>)]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (id, values) in tables {
+        for byte in std::iter::once(*id).chain(values.iter().flat_map(|value| value.to_be_bytes())) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+// The libjpeg quality setting that gives this luminance table, if one does. libjpeg scales the
+// standard table by 5000 / quality percent below 50, and 200 - 2 * quality above, rounding, and
+// keeps every value between 1 and 255. Ref: jcparam.c, jpeg_quality_scaling and
+// jpeg_add_quant_table
+fn libjpeg_quality(table: &[u16]) -> Option<u32> {
+    (1..=100).find(|&quality| {
+        let scale = if quality < 50 { 5000 / quality } else { 200 - quality * 2 };
+        STANDARD_LUMINANCE.iter().zip(table).all(|(&standard, &value)| {
+            ((standard as u32 * scale + 50) / 100).clamp(1, 255) == value as u32
+        })
+    })
+}
@@ -0,0 +1,513 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::error::{ExifError, Result};
+use crate::mode::ParseMode;
+use crate::tag::TagId;
+use crate::text::decode_text;
+use crate::tiff::{slice_at, to_usize, ByteOrder, TiffVariant};
+use crate::value::type_size;
+
+// Image File Directory, source: https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf
+// https://www.exif.org/Exif2-2.PDF, 4.6.2 IFD Structure
+// Clippy would rather this be called Ifd, but IFD is what the spec calls it.
+//
+// count and value_offset are 4 bytes each in a plain TIFF, and 8 in a BigTIFF, so we hold them as
+// u64s either way, and remember which layout the entry came from.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Default)]
+pub struct IFD {
+    pub tag: u16,
+    pub tag_type: u16,
+    pub count: u64,
+    pub value_offset: u64,
+    pub variant: TiffVariant,
+}
+
+// TryFrom for IFD. This enables IFD::try_from(&[u8]) (an IFD struct from a u8 slice). This assumes
+// little endian bytes, like the Intel ("II") files we started out with. IFD::parse handles either.
+// It's TryFrom rather than From because the slice might be too short, and From isn't allowed to
+// fail. Our only other option there would be to panic.
+impl TryFrom<&[u8]> for IFD {
+    type Error = ExifError;
+
+    fn try_from(v: &[u8]) -> Result<Self> {
+        if v.len() < 12 {
+            return Err(ExifError::TruncatedBuffer("IFD entry"));
+        }
+        // [u8; 2] is an array comprised of u8 values, here assigned a length 2 array filled with 0s.
+        // This section is a bit kludgey, since it'd be cool to be able to instantiate an array
+        // from an arbitrary slice. Arrays are typed over some contiguous type and a length, and
+        // since slices are arbitrary over the length of a program (&v[..1], &v[2..e], etc), I can
+        // see why we want to be careful.
+        let mut tag_bytes: [u8; 2] = [0; 2];
+        tag_bytes.copy_from_slice(&v[..2]);
+
+        let mut type_bytes: [u8; 2] = [0; 2];
+        type_bytes.copy_from_slice(&v[2..4]);
+
+        let mut count_bytes: [u8; 4] = [0; 4];
+        count_bytes.copy_from_slice(&v[4..8]);
+
+        let mut value_offset_bytes: [u8; 4] = [0; 4];
+        value_offset_bytes.copy_from_slice(&v[8..12]);
+
+        Ok(IFD {
+            tag: u16::from_le_bytes(tag_bytes),
+            tag_type: u16::from_le_bytes(type_bytes),
+            count: u32::from_le_bytes(count_bytes) as u64,
+            value_offset: u32::from_le_bytes(value_offset_bytes) as u64,
+            variant: TiffVariant::Classic
+        })
+    }
+}
+
+// LowerHex formatter for our IFD struct.
+// We implement this formatter so that we can print out this struct with println!("{:x}", ifd);
+impl fmt::LowerHex for IFD {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tag = self.tag.to_le_bytes();
+        let tag_type = self.tag_type.to_le_bytes();
+        // Only as wide as they were in the file.
+        let width = self.variant.offset_size();
+        let count = &self.count.to_le_bytes()[..width];
+        let value_offset = &self.value_offset.to_le_bytes()[..width];
+
+        // :02 is format width, so we print '0f' instead of just 'f'
+        let fields = format!("{:02x?}{:02x?}{:02x?}{:02x?}", tag, tag_type, count, value_offset);
+        write!(f, "{}", fields)
+    }
+}
+
+// Default formatter for our IFD struct.
+// We implement this formatter so that we can print out this struct with println!("{}", ifd);
+impl fmt::Display for IFD {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fields = format!("{}, {}, {}, {}", self.tag, self.tag_type, self.count, self.value_offset);
+        write!(f, "{}", fields)
+    }
+}
+
+// Methods on struct IFD.
+impl IFD {
+    // What this entry's tag is called. Needs to know which kind of directory the entry came from,
+    // since tag numbers get reused between some of them.
+    pub fn tag_id(&self, kind: IfdKind) -> TagId {
+        TagId::from_id(kind, self.tag)
+    }
+
+    // Like TryFrom, but for either byte order, and either layout.
+    pub fn parse(v: &[u8], byte_order: ByteOrder, variant: TiffVariant) -> Result<Self> {
+        let width = variant.offset_size();
+        if v.len() < variant.entry_size() {
+            return Err(ExifError::TruncatedBuffer("IFD entry"));
+        }
+        Ok(IFD {
+            tag: byte_order.read_u16(&v[..2]),
+            tag_type: byte_order.read_u16(&v[2..4]),
+            count: variant.offset_at(byte_order, v, 4, "IFD entry count")?,
+            value_offset: variant.offset_at(byte_order, v, 4 + width, "IFD entry value")?,
+            variant
+        })
+    }
+
+    // The value_offset field as the bytes it was read from, for values small enough to be stored
+    // in it. That's 4 bytes in a plain TIFF, and 8 in a BigTIFF.
+    pub fn inline_bytes(&self, byte_order: ByteOrder) -> Vec<u8> {
+        match self.variant {
+            TiffVariant::Classic => byte_order.u32_bytes(self.value_offset as u32).to_vec(),
+            TiffVariant::Big => byte_order.u64_bytes(self.value_offset).to_vec(),
+        }
+    }
+
+    // Where the value is, relative to the tiff header, if it's somewhere else. None if it's small
+    // enough to be stored inline, or if it's a type we don't know the size of, since then we can't
+    // tell which.
+    pub fn data_offset(&self) -> Option<u64> {
+        let length = self.data_length()?;
+        if length <= self.variant.offset_size() as u64 {
+            None
+        } else {
+            Some(self.value_offset)
+        }
+    }
+
+    // The offset a pointer tag (the Exif IFD pointer, say, or the thumbnail's offset) holds. It's a
+    // value like any other, stored inline, so it has to be read according to its type: in a
+    // BigTIFF, a 4 byte LONG only fills half the 8 byte value_offset field, and in a big endian
+    // one that's the top half, which would read as the offset times 2^32. Anything that isn't a
+    // single SHORT, LONG or IFD is taken as the whole field, which is what it is for LONG8s and
+    // IFD8s, and is our best guess for the rest.
+    pub fn pointer(&self, byte_order: ByteOrder) -> u64 {
+        let inline = self.inline_bytes(byte_order);
+        match (self.tag_type, self.count) {
+            (3, 1) => byte_order.read_u16(&inline[..2]) as u64,
+            (4 | 13, 1) => byte_order.read_u32(&inline[..4]) as u64,
+            _ => self.value_offset
+        }
+    }
+
+    // How many bytes the value takes up, wherever it is, if we know the size of its type.
+    pub fn data_length(&self) -> Option<u64> {
+        (type_size(self.tag_type)? as u64).checked_mul(self.count)
+    }
+
+    // Basically a constructor (takes no `self` parameter, and returns a Self)
+    // This wraps parse above. Technically we can just do this wherever we wanted to generate an
+    // IFD, but I decided parameterizing from over from_offset was a nice to have. slice_at hands
+    // back None instead of panicking when the range doesn't fit in buf.
+    pub fn from_offset(buf: &[u8], offset: usize, byte_order: ByteOrder, variant: TiffVariant) -> Result<Self> {
+        let bytes = slice_at(buf, offset, variant.entry_size()).ok_or(ExifError::TruncatedBuffer("IFD entry"))?;
+        IFD::parse(bytes, byte_order, variant)
+    }
+
+    // Read this entry's value as text. Takes `tiff`, which starts at the tiff header, since we may
+    // need to actually go get that value from some other location (as designated from
+    // value_offset). "May", because according to the spec, if the value takes up 4 bytes or less,
+    // it's stored right there in the value_offset field (the latter 4 bytes of the entry) instead
+    // of an offset. We parsed those bytes as a u32 in the file's byte order, so we turn them back
+    // into bytes the same way. A 4 byte string like "R98\0" would otherwise be read as the offset
+    // 3684690, and send us off somewhere random.
+    //
+    // (In a BigTIFF, it's 8 bytes or less, and 8 bytes of value_offset.)
+    //
+    // count is in bytes here, which is only right for ASCII (and BYTE and UNDEFINED). Anything
+    // else should go through TagValue::decode. Strings stop at their NUL terminator.
+    //
+    // The offset might be garbage (it might have been written in the wrong endianness, say), and
+    // point past the end of the buffer. Slicing with [..] would panic there. I... guess this'd be
+    // a segfault in C? Traipsing off the far end of a heap allocated byte buffer because your
+    // endianness was wrong? idk. slice_at checks for us (overflow included), so we can return an
+    // error instead.
+    pub fn value_string(&self, tiff: &[u8], byte_order: ByteOrder) -> Result<String> {
+        let length = to_usize(self.count, "tag value")?;
+        let inline = self.inline_bytes(byte_order);
+        let bytes = if length <= inline.len() {
+            &inline[..length]
+        } else {
+            slice_at(tiff, to_usize(self.value_offset, "tag value")?, length).ok_or(ExifError::InvalidOffset("tag value"))?
+        };
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(decode_text(&bytes[..end]).into_owned())
+    }
+}
+
+// Which directory a Directory is. The numbered ones are chained together by next directory
+// offsets (IFD0 is the main image, IFD1 the thumbnail), and the rest hang off a pointer tag in
+// another directory: the Exif IFD and GPS IFD from IFD0, and the Interoperability IFD from the
+// Exif IFD. Raw camera files (and DNGs) also put extra images, often the raw data itself, in
+// directories listed by IFD0's SubIFDs tag, which we number in the order they're listed. Tag
+// numbers only mean something within their directory (tag 1 is GPSLatitudeRef in the GPS IFD,
+// but InteroperabilityIndex in the Interop IFD), so it's worth knowing which is which.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IfdKind {
+    Ifd(usize),
+    Exif,
+    Gps,
+    Interop,
+    SubIfd(usize),
+}
+
+impl fmt::Display for IfdKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IfdKind::Ifd(n) => write!(f, "IFD{}", n),
+            IfdKind::Exif => write!(f, "ExifIFD"),
+            IfdKind::Gps => write!(f, "GPS"),
+            IfdKind::Interop => write!(f, "InteropIFD"),
+            IfdKind::SubIfd(n) => write!(f, "SubIFD{}", n),
+        }
+    }
+}
+
+// What to do when a directory has the same tag more than once. The spec doesn't allow it (entries
+// have to be in strictly increasing tag order), but some writers append a corrected tag rather
+// than replacing the old one, and some editors leave both behind. There's no telling which one
+// the file's author meant, so it's up to whoever's asking.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Duplicates {
+    // The one stored first. Readers that stop at the first match (which is most of them) see this.
+    #[default]
+    First,
+    // The one stored last, on the theory that it was written later.
+    Last,
+    // Every one of them.
+    All,
+}
+
+impl fmt::Display for Duplicates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Duplicates::First => write!(f, "first"),
+            Duplicates::Last => write!(f, "last"),
+            Duplicates::All => write!(f, "all"),
+        }
+    }
+}
+
+// A whole image file directory. The IFD struct above is really one entry in one of these, but it's
+// named after the spec's "IFD Structure" diagram, which is all about entries. A directory is laid
+// out as:
+//
+//   2 bytes      number of entries, n
+//   12 * n bytes the entries themselves, each one an IFD as above
+//   4 bytes      offset of the next directory, or 0 if this is the last one
+//
+// or in a BigTIFF, an 8 byte count, 20 byte entries, and an 8 byte next directory offset.
+//
+// The next directory offset is what chains IFD0 (the main image) to IFD1 (the thumbnail).
+pub struct Directory {
+    pub kind: IfdKind,
+    // Where this directory starts, relative to the tiff header.
+    pub offset: usize,
+    pub entries: Vec<IFD>,
+    pub next_offset: u64,
+}
+
+impl Directory {
+    // Read a whole directory, starting at offset. `tiff` starts at the tiff header, since that's
+    // what offsets are relative to. We check the entries fit before reading any of them, since a
+    // bad offset or entry count would otherwise have us slicing off the end of the buffer.
+    //
+    // Some writers chop off the next directory offset of the last directory, so if it's missing
+    // we treat it as the 0 it should have been rather than throwing away good entries. Strictly,
+    // that's an error, as are entries that aren't sorted by tag (4.6.2 says they must be).
+    pub fn read(tiff: &[u8], offset: usize, kind: IfdKind, byte_order: ByteOrder, variant: TiffVariant, mode: ParseMode) -> Result<Self> {
+        if slice_at(tiff, offset, variant.count_size()).is_none() {
+            return Err(ExifError::InvalidOffset("IFD"));
+        }
+        let count = match variant {
+            TiffVariant::Classic => byte_order.u16_at(tiff, offset, "IFD entry count")? as usize,
+            TiffVariant::Big => to_usize(byte_order.u64_at(tiff, offset, "IFD entry count")?, "IFD entries")?,
+        };
+        // We know the count fits from above. A plain TIFF's count * 12 is at most 786420, so only
+        // a BigTIFF's 64 bit count can make these overflow.
+        let entry_size = variant.entry_size();
+        let entries_start = offset + variant.count_size();
+        let entries_end = count.checked_mul(entry_size)
+            .and_then(|length| entries_start.checked_add(length))
+            .ok_or(ExifError::InvalidOffset("IFD entries"))?;
+        if entries_end > tiff.len() {
+            return Err(ExifError::TruncatedBuffer("IFD entries"));
+        }
+        let entries = (0..count)
+            .map(|i| IFD::from_offset(tiff, entries_start + i * entry_size, byte_order, variant))
+            .collect::<Result<Vec<IFD>>>()?;
+        let next_offset = match variant.offset_at(byte_order, tiff, entries_end, "next IFD offset") {
+            Ok(next_offset) => next_offset,
+            Err(e) if mode.is_strict() => return Err(e),
+            Err(_) => 0
+        };
+        if mode.is_strict() && !entries.windows(2).all(|pair| pair[0].tag < pair[1].tag) {
+            return Err(ExifError::SpecViolation("IFD entries aren't in increasing tag order"));
+        }
+        Ok(Directory { kind, offset, entries, next_offset })
+    }
+
+    // Where entry number i starts, relative to the tiff header. Directories are a count, then
+    // entries of a fixed size, so it's simple arithmetic, given which layout we're reading.
+    pub fn entry_offset(&self, i: usize, variant: TiffVariant) -> usize {
+        self.offset + variant.count_size() + i * variant.entry_size()
+    }
+
+    // The entries with this tag, according to the duplicates policy: one for First or Last, every
+    // one for All, and none if the tag isn't here.
+    pub fn entries_for(&self, tag: u16, duplicates: Duplicates) -> Vec<&IFD> {
+        let mut matching = self.entries.iter().filter(|entry| entry.tag == tag);
+        match duplicates {
+            Duplicates::First => matching.next().into_iter().collect(),
+            Duplicates::Last => matching.next_back().into_iter().collect(),
+            Duplicates::All => matching.collect(),
+        }
+    }
+
+    // Every tag that appears more than once, and how many times, in the order they first appear.
+    pub fn duplicates(&self) -> Vec<(u16, usize)> {
+        let mut counts: Vec<(u16, usize)> = Vec::new();
+        for entry in &self.entries {
+            match counts.iter_mut().find(|(tag, _)| *tag == entry.tag) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((entry.tag, 1)),
+            }
+        }
+        counts.retain(|&(_, n)| n > 1);
+        counts
+    }
+
+    // Follow the chain of directories starting at offset, until we reach a next offset of 0.
+    //
+    // A broken file can point a directory back at itself (or an earlier one), which would have us
+    // going round in circles forever, so we stop if we see an offset twice. If a directory after
+    // the first can't be read we stop there too, and keep what we've got, since a mangled
+    // thumbnail directory shouldn't cost us the main one. A bad first directory is still an error,
+    // and in strict mode, so are the rest.
+    pub fn read_chain(tiff: &[u8], offset: usize, byte_order: ByteOrder, variant: TiffVariant, mode: ParseMode) -> Result<Vec<Self>> {
+        let mut directories: Vec<Directory> = Vec::new();
+        let mut offset = offset;
+        while offset != 0 {
+            if directories.iter().any(|directory| directory.offset == offset) {
+                if mode.is_strict() {
+                    return Err(ExifError::SpecViolation("IFD chain loops back on itself"));
+                }
+                break;
+            }
+            let directory = Directory::read(tiff, offset, IfdKind::Ifd(directories.len()), byte_order, variant, mode)
+                .and_then(|directory| Ok((to_usize(directory.next_offset, "next IFD")?, directory)));
+            match directory {
+                Ok((next_offset, directory)) => {
+                    offset = next_offset;
+                    directories.push(directory);
+                },
+                Err(e) if directories.is_empty() || mode.is_strict() => return Err(e),
+                Err(_) => break
+            }
+        }
+        Ok(directories)
+    }
+
+    // Follow a pointer tag (like the Exif IFD pointer, 0x8769) in this directory to the directory
+    // it points at. Its value is the sub-directory's offset, relative to the tiff header
+    // like everything else. Sub-directories don't chain, so we just read the one. Ok(None) if
+    // there's no pointer. Like read_chain, a broken sub-directory is treated as missing rather
+    // than failing the whole parse, unless we're being strict.
+    pub fn read_sub_ifd(&self, tiff: &[u8], tag: u16, kind: IfdKind, byte_order: ByteOrder, variant: TiffVariant, mode: ParseMode) -> Result<Option<Self>> {
+        let pointer = match self.entries.iter().find(|entry| entry.tag == tag) {
+            Some(pointer) => pointer,
+            None => return Ok(None)
+        };
+        let directory = to_usize(pointer.pointer(byte_order), "sub-IFD")
+            .and_then(|offset| Directory::read(tiff, offset, kind, byte_order, variant, mode));
+        match directory {
+            Ok(directory) => Ok(Some(directory)),
+            Err(e) if mode.is_strict() => Err(e),
+            Err(_) => Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LE: ByteOrder = ByteOrder::LittleEndian;
+
+    // A plain tiff directory at offset 8 (after a header's worth of padding) with these entries,
+    // and this next directory offset.
+    fn directory(entries: &[[u8; 12]], next: u32) -> Vec<u8> {
+        let mut tiff = vec![0; 8];
+        tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for entry in entries {
+            tiff.extend_from_slice(entry);
+        }
+        tiff.extend_from_slice(&next.to_le_bytes());
+        tiff
+    }
+
+    fn entry(tag: u16, tag_type: u16, count: u32, value_offset: u32) -> [u8; 12] {
+        let mut entry = [0; 12];
+        entry[..2].copy_from_slice(&tag.to_le_bytes());
+        entry[2..4].copy_from_slice(&tag_type.to_le_bytes());
+        entry[4..8].copy_from_slice(&count.to_le_bytes());
+        entry[8..].copy_from_slice(&value_offset.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    fn parse_refuses_truncated_entries() {
+        let bytes = entry(0x010f, 2, 6, 26);
+        for length in 0..12 {
+            assert!(IFD::parse(&bytes[..length], LE, TiffVariant::Classic).is_err());
+        }
+        assert!(IFD::parse(&[0; 19], LE, TiffVariant::Big).is_err());
+        assert!(IFD::from_offset(&bytes, 1, LE, TiffVariant::Classic).is_err());
+        assert!(IFD::from_offset(&bytes, usize::MAX, LE, TiffVariant::Classic).is_err());
+    }
+
+    #[test]
+    fn parse_keeps_huge_counts_and_offsets() {
+        let entry = IFD::parse(&entry(0x010f, 5, u32::MAX, u32::MAX), LE, TiffVariant::Classic).unwrap();
+        assert_eq!((entry.count, entry.value_offset), (u32::MAX as u64, u32::MAX as u64));
+        let mut big = [0xff; 20];
+        big[2..4].copy_from_slice(&12u16.to_le_bytes());
+        let entry = IFD::parse(&big, LE, TiffVariant::Big).unwrap();
+        assert_eq!((entry.count, entry.value_offset), (u64::MAX, u64::MAX));
+        // 8 bytes a value times u64::MAX values doesn't fit in a u64.
+        assert_eq!(entry.data_length(), None);
+        assert_eq!(entry.data_offset(), None);
+    }
+
+    #[test]
+    fn pointers_are_read_by_type() {
+        // A LONG in a big endian BigTIFF is in the top half of value_offset.
+        let mut bytes = [0; 20];
+        bytes[..4].copy_from_slice(&[0x87, 0x69, 0, 4]);
+        bytes[11] = 1;
+        bytes[12..16].copy_from_slice(&84u32.to_be_bytes());
+        let pointer = IFD::parse(&bytes, ByteOrder::BigEndian, TiffVariant::Big).unwrap();
+        assert_eq!(pointer.value_offset, 84 << 32);
+        assert_eq!(pointer.pointer(ByteOrder::BigEndian), 84);
+        // And a SHORT in a big endian plain tiff.
+        let pointer = IFD::parse(&[0x87, 0x69, 0, 3, 0, 0, 0, 1, 0, 84, 0, 0], ByteOrder::BigEndian, TiffVariant::Classic).unwrap();
+        assert_eq!(pointer.pointer(ByteOrder::BigEndian), 84);
+        let pointer = IFD::parse(&entry(0x8769, 4, 1, 26), LE, TiffVariant::Classic).unwrap();
+        assert_eq!(pointer.pointer(LE), 26);
+    }
+
+    #[test]
+    fn read_refuses_bad_offsets_and_counts() {
+        let tiff = directory(&[entry(0x010f, 2, 6, 26)], 0);
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            assert!(Directory::read(&tiff, 8, IfdKind::Ifd(0), LE, TiffVariant::Classic, mode).is_ok());
+            assert!(Directory::read(&tiff, tiff.len(), IfdKind::Ifd(0), LE, TiffVariant::Classic, mode).is_err());
+            assert!(Directory::read(&tiff, u32::MAX as usize, IfdKind::Ifd(0), LE, TiffVariant::Classic, mode).is_err());
+            assert!(Directory::read(&tiff, usize::MAX, IfdKind::Ifd(0), LE, TiffVariant::Classic, mode).is_err());
+        }
+        // A count of 0xffff entries, with one there.
+        let mut tiff = directory(&[entry(0x010f, 2, 6, 26)], 0);
+        tiff[8..10].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(Directory::read(&tiff, 8, IfdKind::Ifd(0), LE, TiffVariant::Classic, ParseMode::Lenient).is_err());
+        // A BigTIFF count near u64::MAX, which would overflow multiplied by the entry size.
+        let mut tiff = vec![0; 8];
+        tiff.extend_from_slice(&(u64::MAX - 1).to_le_bytes());
+        assert!(Directory::read(&tiff, 8, IfdKind::Ifd(0), LE, TiffVariant::Big, ParseMode::Lenient).is_err());
+    }
+
+    #[test]
+    fn read_copes_with_a_missing_next_offset_unless_strict() {
+        let mut tiff = directory(&[entry(0x010f, 2, 6, 26)], 0);
+        tiff.truncate(tiff.len() - 4);
+        let directory = Directory::read(&tiff, 8, IfdKind::Ifd(0), LE, TiffVariant::Classic, ParseMode::Lenient).unwrap();
+        assert_eq!((directory.entries.len(), directory.next_offset), (1, 0));
+        assert!(Directory::read(&tiff, 8, IfdKind::Ifd(0), LE, TiffVariant::Classic, ParseMode::Strict).is_err());
+        // Cut off partway through the entry, it's an error either way.
+        tiff.truncate(tiff.len() - 1);
+        assert!(Directory::read(&tiff, 8, IfdKind::Ifd(0), LE, TiffVariant::Classic, ParseMode::Lenient).is_err());
+    }
+
+    #[test]
+    fn read_chain_stops_at_loops() {
+        // A directory whose next offset is itself.
+        let tiff = directory(&[entry(0x010f, 2, 6, 26)], 8);
+        let chain = Directory::read_chain(&tiff, 8, LE, TiffVariant::Classic, ParseMode::Lenient).unwrap();
+        assert_eq!(chain.len(), 1);
+        assert!(Directory::read_chain(&tiff, 8, LE, TiffVariant::Classic, ParseMode::Strict).is_err());
+
+        // Two that point at each other.
+        let mut tiff = directory(&[entry(0x010f, 2, 6, 26)], 26);
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&entry(0x0110, 2, 6, 26));
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        let chain = Directory::read_chain(&tiff, 8, LE, TiffVariant::Classic, ParseMode::Lenient).unwrap();
+        assert_eq!(chain.len(), 2);
+        assert!(Directory::read_chain(&tiff, 8, LE, TiffVariant::Classic, ParseMode::Strict).is_err());
+    }
+
+    #[test]
+    fn read_chain_keeps_what_it_has_when_a_later_directory_is_bad() {
+        let tiff = directory(&[entry(0x010f, 2, 6, 26)], u32::MAX);
+        let chain = Directory::read_chain(&tiff, 8, LE, TiffVariant::Classic, ParseMode::Lenient).unwrap();
+        assert_eq!(chain.len(), 1);
+        assert!(Directory::read_chain(&tiff, 8, LE, TiffVariant::Classic, ParseMode::Strict).is_err());
+        assert!(Directory::read_chain(&tiff, u32::MAX as usize, LE, TiffVariant::Classic, ParseMode::Lenient).is_err());
+    }
+}
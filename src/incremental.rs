@@ -0,0 +1,112 @@
+use std::io;
+
+use crate::error::{ExifError, Result};
+use crate::jpeg::{APP1, EOI, EXIF_SIGNATURE, RST0, RST7, SOI, SOS, TEM};
+use crate::mode::ParseMode;
+use crate::Exif;
+
+// Where an IncrementalParser has got to.
+pub enum Progress {
+    // We need at least this many more bytes before we can say anything more. It's a lower bound,
+    // not a promise: once those arrive we may find we need more again.
+    NeedsMoreData(usize),
+    // The exif is all here.
+    Complete(Exif),
+}
+
+// A parser you can feed a jpeg a piece at a time, as it arrives over a network, say. Exif sits
+// near the front of a jpeg, in an APP1 segment before any image data, so usually a few kilobytes
+// of a multi-megabyte file are enough, and we can stop downloading (or buffering) as soon as we
+// have them.
+//
+// We only keep what we've been fed, and only look at it segment by segment: every segment starts
+// with a marker and a length, so we can tell exactly how far away the end of the exif is, or skip
+// segments we don't care about without needing their bytes at all.
+#[derive(Default)]
+pub struct IncrementalParser {
+    buf: Vec<u8>,
+    done: bool,
+    mode: ParseMode,
+}
+
+impl IncrementalParser {
+    pub fn new() -> Self {
+        IncrementalParser::default()
+    }
+
+    // A parser that reads the exif as fussily as asked, once it's all here.
+    pub fn with_mode(mode: ParseMode) -> Self {
+        IncrementalParser { mode, ..IncrementalParser::default() }
+    }
+
+    // Add some more bytes, and see if that's enough. Once this has returned Complete, the parser
+    // is finished with, and feeding it more is an error.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Progress> {
+        if self.done {
+            let e = io::Error::new(io::ErrorKind::InvalidInput, "The exif has already been parsed.");
+            return Err(ExifError::Io(e));
+        }
+        self.buf.extend_from_slice(bytes);
+        let progress = self.progress()?;
+        if let Progress::Complete(_) = progress {
+            self.done = true;
+        }
+        Ok(progress)
+    }
+
+    // Everything we've been fed so far.
+    pub fn bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    // Walk the segments we have, from the top each time. There are only a handful before the
+    // exif, so this is cheap, and it saves us keeping track of where we were.
+    fn progress(&self) -> Result<Progress> {
+        let buf = &self.buf;
+        if buf.len() < 2 {
+            return Ok(Progress::NeedsMoreData(2 - buf.len()));
+        }
+        if buf[..2] != [0xff, SOI] {
+            return Err(ExifError::BadMarker("jpeg SOI marker"));
+        }
+        let mut pos = 2;
+        loop {
+            // A marker and its length.
+            if pos + 4 > buf.len() {
+                return Ok(Progress::NeedsMoreData(pos + 4 - buf.len()));
+            }
+            if buf[pos] != 0xff {
+                return Err(ExifError::BadMarker("jpeg marker"));
+            }
+            let marker = buf[pos + 1];
+            match marker {
+                // Fill byte, the marker proper is next.
+                0xff => pos += 1,
+                TEM | RST0..=RST7 => pos += 2,
+                // Metadata segments all come before the image data, so if we get this far without
+                // finding exif, there isn't any.
+                SOS | EOI => return Err(ExifError::NoExif),
+                _ => {
+                    let length = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]) as usize;
+                    let end = pos + 2 + length;
+                    if marker == APP1 {
+                        // We need the segment's signature to know if this is the exif, and then
+                        // all of it to parse it.
+                        let signature_end = pos + 4 + EXIF_SIGNATURE.len();
+                        if signature_end > buf.len() {
+                            return Ok(Progress::NeedsMoreData(signature_end - buf.len()));
+                        }
+                        if &buf[pos + 4..signature_end] == EXIF_SIGNATURE {
+                            if end > buf.len() {
+                                return Ok(Progress::NeedsMoreData(end - buf.len()));
+                            }
+                            return Ok(Progress::Complete(Exif::from_bytes_with(&buf[..end], self.mode)?));
+                        }
+                    }
+                    // Some other segment. We don't need its bytes, just to know where it ends.
+                    pos = end;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::ops::Range;
+
+use crate::error::{ExifError, Result};
+
+// A few of the JPEG markers we care about. Every marker is 0xff followed by one of these.
+// Ref: https://www.w3.org/Graphics/JPEG/itu-t81.pdf, Table B.1
+pub const SOI: u8 = 0xd8;
+pub const EOI: u8 = 0xd9;
+pub const SOS: u8 = 0xda;
+pub const TEM: u8 = 0x01;
+pub const RST0: u8 = 0xd0;
+pub const RST7: u8 = 0xd7;
+// Define quantization tables.
+pub const DQT: u8 = 0xdb;
+// The application segments, APP0 to APP15. APP1 is where exif (and XMP) lives.
+pub const APP0: u8 = 0xe0;
+pub const APP1: u8 = 0xe1;
+pub const APP14: u8 = 0xee;
+pub const APP15: u8 = 0xef;
+// A comment.
+pub const COM: u8 = 0xfe;
+
+// The first 6 bytes of an APP1 segment's data that holds exif, rather than XMP.
+pub const EXIF_SIGNATURE: &[u8] = b"Exif\0\0";
+
+// What we found when we walked a jpeg's image data looking for its end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageData {
+    // We made it all the way to the EOI (end of image) marker.
+    Complete,
+    // The file ran out before EOI. This is what a half-written file from a failing SD card looks
+    // like: the exif up front is fine, but the picture stops partway down.
+    Truncated,
+}
+
+// Walk from SOI to EOI, to check the image data is all there. Exif lives right at the front of a
+// jpeg, so a file can have perfectly good metadata and still be missing most of its picture.
+pub fn check_image_data(buf: &[u8]) -> Result<ImageData> {
+    match find_eoi(buf)? {
+        Some(_) => Ok(ImageData::Complete),
+        None => Ok(ImageData::Truncated)
+    }
+}
+
+// Find the end of the jpeg that starts at the beginning of buf: the offset just past its EOI
+// marker, or None if buf runs out first. Anything after EOI isn't part of the jpeg (and is
+// ignored by viewers), so this is also how we know where one jpeg stops when several are packed
+// together.
+//
+// A jpeg is a string of segments, each starting with a marker. Most markers are followed by a 2
+// byte big endian length (which counts itself, but not the marker), so we can hop from one to the
+// next. SOS (start of scan) is the odd one out: after its header comes the entropy coded image
+// data, which has no length, so we have to step through it a byte at a time looking for the next
+// marker. Inside that data a real 0xff byte is written as ff 00, and restart markers (RST0-7) can
+// appear between chunks, so neither of those end the scan. Progressive jpegs have several scans,
+// which is why we go back around the loop after each one.
+pub fn find_eoi(buf: &[u8]) -> Result<Option<usize>> {
+    if !buf.starts_with(&[0xff, SOI]) {
+        return Err(ExifError::BadMarker("jpeg SOI marker"));
+    }
+    let mut pos = 2;
+    loop {
+        // Markers may be preceded by any number of 0xff fill bytes.
+        while buf.get(pos) == Some(&0xff) && buf.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        if pos + 2 > buf.len() {
+            return Ok(None);
+        }
+        if buf[pos] != 0xff {
+            return Err(ExifError::BadMarker("jpeg marker"));
+        }
+        let marker = buf[pos + 1];
+        match marker {
+            EOI => return Ok(Some(pos + 2)),
+            // These stand alone, with no length after them.
+            TEM | RST0..=RST7 => pos += 2,
+            _ => {
+                if pos + 4 > buf.len() {
+                    return Ok(None);
+                }
+                let length = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]) as usize;
+                let end = pos + 2 + length;
+                if end > buf.len() {
+                    return Ok(None);
+                }
+                pos = end;
+                if marker == SOS {
+                    pos = skip_entropy_coded_data(buf, pos);
+                }
+            }
+        }
+    }
+}
+
+// Find the exif in a jpeg the proper way, by following its structure: hop from segment to segment
+// by their lengths, the same way find_eoi does, until we reach an APP1 segment that starts with
+// the Exif signature. Returns where the tiff data inside it starts and ends, so the tiff parser
+// only ever sees that segment's payload, not image data that happens to contain "II*\0".
+//
+// Metadata segments all come before the first scan, so if we reach SOS (or EOI) without finding
+// it, there isn't any, and we say so with None. There may be several APP1 segments, since XMP
+// uses APP1 too, which is why we check the signature.
+pub fn find_exif_segment(buf: &[u8]) -> Result<Option<Range<usize>>> {
+    if !buf.starts_with(&[0xff, SOI]) {
+        return Err(ExifError::BadMarker("jpeg SOI marker"));
+    }
+    let mut pos = 2;
+    loop {
+        while buf.get(pos) == Some(&0xff) && buf.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        if pos + 2 > buf.len() {
+            return Err(ExifError::TruncatedBuffer("jpeg marker"));
+        }
+        if buf[pos] != 0xff {
+            return Err(ExifError::BadMarker("jpeg marker"));
+        }
+        let marker = buf[pos + 1];
+        match marker {
+            SOS | EOI => return Ok(None),
+            TEM | RST0..=RST7 => pos += 2,
+            _ => {
+                if pos + 4 > buf.len() {
+                    return Err(ExifError::TruncatedBuffer("jpeg segment length"));
+                }
+                // The length counts its own 2 bytes, so anything less is nonsense.
+                let length = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]) as usize;
+                if length < 2 {
+                    return Err(ExifError::BadMarker("jpeg segment length"));
+                }
+                let end = pos + 2 + length;
+                if end > buf.len() {
+                    return Err(ExifError::TruncatedBuffer("jpeg segment"));
+                }
+                let data = &buf[pos + 4..end];
+                if marker == APP1 && data.starts_with(EXIF_SIGNATURE) {
+                    return Ok(Some(pos + 4 + EXIF_SIGNATURE.len()..end));
+                }
+                pos = end;
+            }
+        }
+    }
+}
+
+// A copy of a jpeg without its metadata: the application segments (exif, XMP, ICC profiles, maker
+// notes, Photoshop's IPTC, and whatever else cameras and editors put there) and comments. The
+// image itself, the scans and the tables between them, is copied as is, bit for bit, so nothing is
+// re-encoded.
+//
+// Two application segments stay, since they're needed to decode the picture rather than describe
+// it: APP0 (JFIF, which says how to interpret the colours and pixel density) and APP14 (Adobe's,
+// which says whether a CMYK jpeg was colour transformed). Without the ICC profile in APP2, a
+// viewer will assume sRGB, so photos shot in a wider colour space can look a little duller.
+//
+// Metadata isn't only found before the first scan. Progressive jpegs can have comments and
+// application segments between their scans, and anything after EOI, like the second image in an
+// MPF file or a phone maker's trailer, isn't part of the picture at all, so we walk the scans the
+// same way find_eoi does, and stop at EOI. A file that runs out before EOI keeps whatever of its
+// picture there is.
+pub fn strip_metadata(buf: &[u8]) -> Result<Vec<u8>> {
+    let (segments, mut pos) = header_segments(buf)?;
+    let mut stripped = vec![0xff, SOI];
+    for (marker, range) in segments {
+        if !is_stripped(marker) {
+            stripped.extend_from_slice(&buf[range]);
+        }
+    }
+    loop {
+        while buf.get(pos) == Some(&0xff) && buf.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        if pos + 2 > buf.len() {
+            stripped.extend_from_slice(&buf[pos..]);
+            return Ok(stripped);
+        }
+        if buf[pos] != 0xff {
+            return Err(ExifError::BadMarker("jpeg marker"));
+        }
+        let marker = buf[pos + 1];
+        let end = match marker {
+            EOI => {
+                stripped.extend_from_slice(&buf[pos..pos + 2]);
+                return Ok(stripped);
+            },
+            TEM | RST0..=RST7 => pos + 2,
+            _ if pos + 4 > buf.len() => buf.len(),
+            _ => {
+                let length = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]) as usize;
+                if length < 2 {
+                    return Err(ExifError::BadMarker("jpeg segment length"));
+                }
+                (pos + 2 + length).min(buf.len())
+            }
+        };
+        let end = if marker == SOS { skip_entropy_coded_data(buf, end) } else { end };
+        if !is_stripped(marker) {
+            stripped.extend_from_slice(&buf[pos..end]);
+        }
+        pos = end;
+    }
+}
+
+// The opposite of strip_metadata: a copy of a jpeg with its metadata segments exactly as they were,
+// but a 1x1 grey placeholder in place of the picture. This is for turning a private photo that
+// trips us up into a test case that can be shared. The tables and frame header describing the
+// original picture go too, since they'd be describing a picture that isn't there.
+//
+// The metadata can have a small copy of the picture in it, though: exif has a thumbnail in IFD1,
+// and JFIF can have one in APP0. Those are left as they are, since they're part of the metadata.
+pub fn minimize(buf: &[u8]) -> Result<Vec<u8>> {
+    let (segments, _) = header_segments(buf)?;
+    let mut minimized = vec![0xff, SOI];
+    for (marker, range) in segments {
+        if is_metadata(marker) {
+            minimized.extend_from_slice(&buf[range]);
+        }
+    }
+    minimized.extend_from_slice(PLACEHOLDER);
+    Ok(minimized)
+}
+
+// Everything after SOI in the smallest jpeg we could write by hand: one grey pixel. That's a
+// quantization table (all 1s), a frame header for a 1x1 single component baseline image, a DC and
+// an AC Huffman table that each have one code, 0, for "no difference" and "end of block", and a
+// scan whose data is those two codes, padded out with 1s.
+const PLACEHOLDER: &[u8] = &[
+    // DQT, table 0, 8 bit.
+    0xff, 0xdb, 0x00, 0x43, 0x00,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    // SOF0: 8 bit, 1 high, 1 wide, 1 component (id 1, no subsampling, quantization table 0).
+    0xff, 0xc0, 0x00, 0x0b, 0x08, 0x00, 0x01, 0x00, 0x01, 0x01, 0x01, 0x11, 0x00,
+    // DHT, DC table 0: one code of length 1, for category 0.
+    0xff, 0xc4, 0x00, 0x14, 0x00,
+    1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0x00,
+    // DHT, AC table 0: one code of length 1, for end of block.
+    0xff, 0xc4, 0x00, 0x14, 0x10,
+    1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0x00,
+    // SOS: 1 component, using tables 0 and 0, over the whole spectrum.
+    0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3f, 0x00,
+    // 0 (DC), 0 (EOB), and six bits of padding.
+    0x3f,
+    0xff, EOI,
+];
+
+// Application segments and comments. These describe a picture, rather than being needed to draw it.
+fn is_metadata(marker: u8) -> bool {
+    matches!(marker, APP0..=APP15 | COM)
+}
+
+// The segments strip_metadata leaves out: all the metadata but APP0 and APP14.
+fn is_stripped(marker: u8) -> bool {
+    is_metadata(marker) && marker != APP0 && marker != APP14
+}
+
+// A segment's marker, and where it is, marker included.
+pub type Segment = (u8, Range<usize>);
+
+// The segments before the first scan, and the offset of the marker that ends them: the first SOS,
+// where the picture starts, or EOI, if there isn't one. Fill bytes between segments aren't part of
+// either, so they're dropped.
+pub fn header_segments(buf: &[u8]) -> Result<(Vec<Segment>, usize)> {
+    if !buf.starts_with(&[0xff, SOI]) {
+        return Err(ExifError::BadMarker("jpeg SOI marker"));
+    }
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    loop {
+        while buf.get(pos) == Some(&0xff) && buf.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        if pos + 2 > buf.len() {
+            return Err(ExifError::TruncatedBuffer("jpeg marker"));
+        }
+        if buf[pos] != 0xff {
+            return Err(ExifError::BadMarker("jpeg marker"));
+        }
+        let marker = buf[pos + 1];
+        match marker {
+            SOS | EOI => return Ok((segments, pos)),
+            TEM | RST0..=RST7 => {
+                segments.push((marker, pos..pos + 2));
+                pos += 2;
+            },
+            _ => {
+                if pos + 4 > buf.len() {
+                    return Err(ExifError::TruncatedBuffer("jpeg segment length"));
+                }
+                let length = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]) as usize;
+                if length < 2 {
+                    return Err(ExifError::BadMarker("jpeg segment length"));
+                }
+                let end = pos + 2 + length;
+                if end > buf.len() {
+                    return Err(ExifError::TruncatedBuffer("jpeg segment"));
+                }
+                segments.push((marker, pos..end));
+                pos = end;
+            }
+        }
+    }
+}
+
+// Step through entropy coded data, and return the offset of the marker that ends it (or the end of
+// the buffer, if nothing does).
+fn skip_entropy_coded_data(buf: &[u8], mut pos: usize) -> usize {
+    while pos + 1 < buf.len() {
+        if buf[pos] == 0xff {
+            match buf[pos + 1] {
+                0x00 | RST0..=RST7 => pos += 2,
+                // More fill, the marker proper comes after it.
+                0xff => pos += 1,
+                _ => return pos
+            }
+        } else {
+            pos += 1;
+        }
+    }
+    buf.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXIF_JPG: &[u8] = include_bytes!("../fixtures/exif.jpg");
+
+    // A progressive-looking jpeg: the placeholder's tables and frame header, then two scans with a
+    // comment and an APP1 between them, then EOI, then a trailer.
+    fn progressive() -> Vec<u8> {
+        let scan = PLACEHOLDER.iter().position(|&b| b == SOS).unwrap() - 1;
+        let mut jpeg = vec![0xff, SOI];
+        jpeg.extend_from_slice(&[0xff, APP0, 0x00, 0x07, b'J', b'F', b'I', b'F', 0x00]);
+        jpeg.extend_from_slice(&EXIF_JPG[2..EXIF_JPG.len() - 2]);
+        jpeg.extend_from_slice(&[0xff, COM, 0x00, 0x05, b'h', b'i', b'!']);
+        jpeg.extend_from_slice(&PLACEHOLDER[..PLACEHOLDER.len() - 2]);
+        jpeg.extend_from_slice(&[0xff, COM, 0x00, 0x05, b'h', b'i', b'!']);
+        jpeg.extend_from_slice(&[0xff, APP1, 0x00, 0x06, b'X', b'M', b'P', 0x00]);
+        jpeg.extend_from_slice(&PLACEHOLDER[scan..]);
+        jpeg.extend_from_slice(b"TRAILER");
+        jpeg
+    }
+
+    fn has_marker(jpeg: &[u8], marker: u8) -> bool {
+        jpeg.windows(2).any(|pair| pair == [0xff, marker])
+    }
+
+    #[test]
+    fn strip_leaves_out_metadata_between_scans_and_after_eoi() {
+        let jpeg = progressive();
+        let stripped = strip_metadata(&jpeg).unwrap();
+        assert!(!has_marker(&stripped, APP1));
+        assert!(!has_marker(&stripped, COM));
+        assert!(!stripped.windows(7).any(|bytes| bytes == b"TRAILER"));
+        assert!(!stripped.windows(4).any(|bytes| bytes == b"XMP\0"));
+        assert!(stripped.starts_with(&[0xff, SOI, 0xff, APP0]));
+        assert!(stripped.ends_with(&[0xff, EOI]));
+        assert_eq!(find_eoi(&stripped).unwrap(), Some(stripped.len()));
+        assert_eq!(find_exif_segment(&stripped).unwrap(), None);
+
+        // What's left is the JFIF segment and the picture, both scans, unchanged.
+        let scan = PLACEHOLDER.iter().position(|&b| b == SOS).unwrap() - 1;
+        let mut expected = vec![0xff, SOI, 0xff, APP0, 0x00, 0x07, b'J', b'F', b'I', b'F', 0x00];
+        expected.extend_from_slice(&PLACEHOLDER[..PLACEHOLDER.len() - 2]);
+        expected.extend_from_slice(&PLACEHOLDER[scan..]);
+        assert_eq!(stripped, expected);
+    }
+
+    #[test]
+    fn strip_keeps_what_there_is_of_a_truncated_picture() {
+        let jpeg = progressive();
+        let eoi = find_eoi(&jpeg).unwrap().unwrap();
+        let stripped = strip_metadata(&jpeg[..eoi - 3]).unwrap();
+        let full = strip_metadata(&jpeg).unwrap();
+        assert_eq!(stripped, &full[..full.len() - 3]);
+    }
+
+    #[test]
+    fn strip_without_a_picture() {
+        let stripped = strip_metadata(EXIF_JPG).unwrap();
+        assert_eq!(stripped, [0xff, SOI, 0xff, EOI]);
+    }
+}
@@ -0,0 +1,603 @@
+// HelloExif, the library. This is the parsing half of the program: hand it some bytes (or a path)
+// and you get back an Exif, which knows where things are in those bytes and can read values out
+// of them. The binary in main.rs is just a wrapper that prints what it finds.
+use std::borrow::Cow;
+use std::io;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::reader::read_metadata;
+use crate::tiff::slice_at;
+use crate::write::{copy_edits, rewrite};
+
+mod bugreport;
+mod carve;
+mod confidence;
+mod datetime;
+mod describe;
+mod error;
+mod filetype;
+mod geotiff;
+mod gps;
+mod heif;
+mod identify;
+mod ifd;
+mod incremental;
+mod jpeg;
+mod makernote;
+mod mode;
+mod png;
+mod rational;
+mod raw;
+mod reader;
+mod structure;
+mod tag;
+mod text;
+mod tiff;
+mod value;
+mod write;
+
+pub use bugreport::BugReport;
+pub use carve::{CarvedJpeg, Carver, MAX_CARVE_SIZE};
+pub use confidence::{Confidence, Evidence};
+pub use datetime::{DateTimeKind, ExifDateTime};
+pub use describe::describe;
+pub use error::{ExifError, Result};
+pub use filetype::FileType;
+pub use geotiff::{GeoKey, GeoKeyValue, GeoTiff, ModelType, Tiepoint};
+pub use gps::GpsInfo;
+pub use heif::find_exif_item;
+pub use identify::{identify, Clue};
+pub use ifd::{Directory, Duplicates, IfdKind, IFD};
+pub use incremental::{IncrementalParser, Progress};
+pub use jpeg::{check_image_data, find_eoi, find_exif_segment, minimize, strip_metadata, ImageData};
+pub use makernote::CanonMakerNote;
+pub use mode::ParseMode;
+pub use png::find_exif_chunk;
+pub use rational::{Rational, SRational};
+pub use raw::{Cr2Header, RawFormat};
+pub use reader::ReadStats;
+pub use structure::{ImageStructure, Layout};
+pub use tag::TagId;
+pub use text::decode_text;
+pub use tiff::{to_usize, ByteOrder, TiffHeader, TiffVariant};
+pub use value::{type_size, TagValue};
+pub use write::Edit;
+
+// What we found in a file. We hold on to the bytes themselves, since values are stored as offsets
+// into them, and we'll need to go back and read from those offsets later.
+pub struct Exif {
+    buf: Vec<u8>,
+    file_type: FileType,
+    tiff_header_offset: usize,
+    // Where the tiff data ends: the end of the APP1 segment it came in, if we found it that way,
+    // or else the end of the file.
+    tiff_end: usize,
+    header: TiffHeader,
+    // Every directory in the chain: IFD0 (the primary image) first, then IFD1 (the thumbnail), if
+    // there is one.
+    ifds: Vec<Directory>,
+    // Directories pointed at by tags rather than chained: the Exif IFD, GPS IFD and
+    // Interoperability IFD, whichever of those the file has.
+    sub_ifds: Vec<Directory>,
+    confidence: Confidence,
+    mode: ParseMode,
+    // Which entry to hand back when a tag turns up more than once in a directory.
+    duplicates: Duplicates,
+    // Where the rest of the file is, if buf is only the start of it. See reader.rs.
+    rest: Option<PathBuf>,
+    // How reading the file has gone, if we came from one: reading buf, and the rest of the file
+    // whenever we've needed it since. That's behind &self, so it needs a lock.
+    read_stats: Mutex<Option<ReadStats>>,
+}
+
+impl Exif {
+    // Read exif from a file on disk, leniently.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Exif::from_path_with(path, ParseMode::Lenient)
+    }
+
+    // Read exif from a file on disk, as fussily as asked. We only read as much of the start of the
+    // file as the metadata needs, where we can tell how much that is, so big files (raw files run
+    // to hundreds of megabytes) cost a few kilobytes of reading. The rest is read if it's needed,
+    // for saving, say. ? after function call here means unwrap result or returns err.
+    pub fn from_path_with<P: AsRef<Path>>(path: P, mode: ParseMode) -> Result<Self> {
+        let mut file = File::open(&path)?;
+        let (buf, length, read_stats) = read_metadata(&mut file)?;
+        let mut exif = Exif::from_bytes_with(&buf, mode)?;
+        exif.read_stats = Mutex::new(Some(read_stats));
+        if (buf.len() as u64) < length {
+            exif.rest = Some(path.as_ref().to_path_buf());
+        }
+        Ok(exif)
+    }
+
+    // Read exif from bytes already in memory, leniently. We take a slice so callers can hand us
+    // whatever they've got, and copy it, since values are read lazily from the buffer later on.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self> {
+        Exif::from_bytes_with(buf, ParseMode::Lenient)
+    }
+
+    // Read exif from bytes already in memory, as fussily as asked.
+    pub fn from_bytes_with(buf: &[u8], mode: ParseMode) -> Result<Self> {
+        // Check what we've actually been handed before we go digging around in it. We know our
+        // way around a JPEG, a PNG, a HEIF, and a TIFF, which exif is a cut-down version of
+        // anyway. Unknown files still get a go, since we'd have tried them before we could tell
+        // the difference anyway.
+        let file_type = FileType::from_magic(buf);
+
+        // Find the tiff header. In a jpeg, we can walk the segments to the APP1 segment holding
+        // the exif, and hand the tiff parser just that. A PNG's chunks get us to its eXIf chunk
+        // the same way, and a HEIF's item boxes to its Exif item. A tiff file is all tiff, header
+        // first, so there's nothing to find, and so are the raw camera formats built on it.
+        let segment = match file_type {
+            FileType::Jpeg => find_exif_segment(buf),
+            FileType::Png => find_exif_chunk(buf, mode),
+            FileType::Heif => find_exif_item(buf),
+            FileType::Tiff | FileType::Cr2 => Ok(Some(0..buf.len())),
+            FileType::Unknown => Ok(None)
+        };
+        let (tiff_header_offset, tiff_end, scanned) = match segment {
+            Ok(Some(range)) => (range.start, range.end, false),
+            Ok(None) if mode.is_strict() => return Err(ExifError::NoExif),
+            Err(e) if mode.is_strict() => return Err(e),
+            // Failing that, we scan for it, like we used to, which copes with broken segments and
+            // things that aren't jpegs at all, at the risk of finding something that isn't exif.
+            // Ref: https://www.media.mit.edu/pia/Research/deepview/exif.html We look for the full
+            // 4 byte signature (byte order and 42) in either byte order, rather than just "II",
+            // since a lone pair of 0x49s turns up in all sorts of places.
+            _ => {
+                let offset = find_tiff_header(buf).ok_or(ExifError::NoExif)?;
+                (offset, buf.len(), true)
+            }
+        };
+        let tiff = &buf[tiff_header_offset..tiff_end];
+        let header = TiffHeader::parse(tiff)?;
+
+        // Then walk the directory the header points us at, and every one chained after it.
+        let ifd0_offset = to_usize(header.ifd0_offset, "IFD0")?;
+        let ifds = Directory::read_chain(tiff, ifd0_offset, header.byte_order, header.variant, mode)?;
+        if ifds.is_empty() {
+            return Err(ExifError::InvalidOffset("IFD0"));
+        }
+
+        // Most of the interesting stuff (exposure, ISO, location) isn't in IFD0 at all, but in
+        // sub-directories that IFD0 points at. The Exif IFD can in turn point at the Interop IFD.
+        let sub_ifds = read_sub_ifds(tiff, &ifds[0], &header, mode)?;
+
+        // If we found the header by scanning for it, it's worth checking it looks like the real
+        // thing.
+        let confidence = Confidence::assess(buf, tiff_header_offset, scanned, &ifds[0]);
+
+        Ok(Exif {
+            buf: buf.to_vec(),
+            file_type,
+            tiff_header_offset,
+            tiff_end,
+            header,
+            ifds,
+            sub_ifds,
+            confidence,
+            mode,
+            duplicates: Duplicates::default(),
+            rest: None,
+            read_stats: Mutex::new(None),
+        })
+    }
+
+    // The bytes we parsed. Read from a path, that's only as much of the start of the file as the
+    // metadata needed, so it may not be the whole file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    // Just the tiff data, from the header on. Value offsets are relative to the start of this.
+    pub fn tiff(&self) -> &[u8] {
+        &self.buf[self.tiff_header_offset..self.tiff_end]
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    // Where the tiff header starts (jpegs have tiff format headers for exif, who knew). Value
+    // offsets are relative to this, not to the start of the file.
+    pub fn tiff_header_offset(&self) -> usize {
+        self.tiff_header_offset
+    }
+
+    pub fn byte_order(&self) -> ByteOrder {
+        self.header.byte_order
+    }
+
+    // Where IFD0 starts, relative to the tiff header.
+    pub fn ifd0_offset(&self) -> usize {
+        self.ifds[0].offset
+    }
+
+    // Plain TIFF, or BigTIFF.
+    pub fn variant(&self) -> TiffVariant {
+        self.header.variant
+    }
+
+    // How fussy we were when we read it.
+    pub fn mode(&self) -> ParseMode {
+        self.mode
+    }
+
+    // Which of a duplicated tag's entries lookups hand back. This only changes how we look things
+    // up, not what we read, so it can be changed whenever.
+    pub fn duplicates(&self) -> Duplicates {
+        self.duplicates
+    }
+
+    pub fn set_duplicates(&mut self, duplicates: Duplicates) {
+        self.duplicates = duplicates;
+    }
+
+    // How sure we are this is real exif, and why.
+    pub fn confidence(&self) -> &Confidence {
+        &self.confidence
+    }
+
+    // How reading the file has gone: how much of it we've read, and how often the same bytes were
+    // wanted twice. Reading all of it again (to check the image data, say) counts too. None if we
+    // weren't read from a file, or have been edited since.
+    pub fn read_stats(&self) -> Option<ReadStats> {
+        self.read_stats.lock().map_or(None, |stats| *stats)
+    }
+
+    // Every directory we found, in chain order. There's always at least one, IFD0.
+    pub fn ifds(&self) -> &[Directory] {
+        &self.ifds
+    }
+
+    // Every directory we found, chained ones first, then the ones pointed at by tags.
+    pub fn directories(&self) -> impl Iterator<Item = &Directory> {
+        self.ifds.iter().chain(self.sub_ifds.iter())
+    }
+
+    // A particular directory, if the file has it.
+    pub fn directory(&self, kind: IfdKind) -> Option<&Directory> {
+        self.directories().find(|directory| directory.kind == kind)
+    }
+
+    pub fn exif_ifd(&self) -> Option<&Directory> {
+        self.directory(IfdKind::Exif)
+    }
+
+    pub fn gps_ifd(&self) -> Option<&Directory> {
+        self.directory(IfdKind::Gps)
+    }
+
+    pub fn interop_ifd(&self) -> Option<&Directory> {
+        self.directory(IfdKind::Interop)
+    }
+
+    // Every entry in IFD0, in the order they're stored.
+    pub fn entries(&self) -> &[IFD] {
+        &self.ifds[0].entries
+    }
+
+    // The entry with this tag, if there is one, looked for in the directory the spec puts it in.
+    // Tags we don't know are looked for in IFD0. If the tag is there more than once, which one we
+    // pick depends on the duplicates policy. There's only room for one here, so All gets the
+    // first; use entries_for to see them all.
+    pub fn entry(&self, tag: TagId) -> Option<&IFD> {
+        self.entry_in(tag.home().unwrap_or(IfdKind::Ifd(0)), tag)
+    }
+
+    // Every entry with this tag the duplicates policy lets through, from the tag's home directory.
+    pub fn entries_for(&self, tag: TagId) -> Vec<&IFD> {
+        match self.directory(tag.home().unwrap_or(IfdKind::Ifd(0))) {
+            Some(directory) => directory.entries_for(tag.id(), self.duplicates),
+            None => Vec::new()
+        }
+    }
+
+    // Decode an entry's value, according to its type. Bytes and text borrow from our copy of the
+    // file, see TagValue.
+    pub fn value(&self, entry: &IFD) -> Result<TagValue<'_>> {
+        TagValue::decode(entry, self.tiff(), self.header.byte_order)
+    }
+
+    // Walk the image data to see if it's all there. Only makes sense for jpegs.
+    pub fn image_data(&self) -> Result<ImageData> {
+        match self.file_type {
+            FileType::Jpeg => check_image_data(&self.whole_file()?),
+            file_type => Err(ExifError::UnsupportedFormat(file_type))
+        }
+    }
+
+    // The entry with this tag in a particular directory, if the file has both, picked like entry
+    // does.
+    pub fn entry_in(&self, kind: IfdKind, tag: TagId) -> Option<&IFD> {
+        self.directory(kind)?.entries_for(tag.id(), self.duplicates).into_iter().next()
+    }
+
+    // The Make tag's value. Ok(None) if there isn't one, and an error if there is, but it couldn't
+    // be read.
+    pub fn make(&self) -> Result<Option<String>> {
+        match self.entry(TagId::Make) {
+            Some(entry) => self.value_string(entry).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    // The thumbnail jpeg IFD1 points at, if there is one, and it's all there. Its offset is
+    // relative to the tiff header, like every other, and it's a whole jpeg, SOI to EOI, so the
+    // bytes can be written straight out to a file. Ref: https://www.exif.org/Exif2-2.PDF, 4.5.5.
+    pub fn thumbnail(&self) -> Option<&[u8]> {
+        let number = |tag: TagId| -> Option<usize> {
+            let entry = self.entry_in(IfdKind::Ifd(1), tag)?;
+            let value = self.value(entry).ok()?.to_u64s()?.first().copied()?;
+            to_usize(value, "thumbnail").ok()
+        };
+        let offset = number(TagId::JPEGInterchangeFormat)?;
+        let length = number(TagId::JPEGInterchangeFormatLength)?;
+        slice_at(self.tiff(), offset, length)
+    }
+
+    // How the image a numbered directory describes is laid out. See ImageStructure.
+    pub fn image_structure(&self, directory: &Directory) -> Result<ImageStructure> {
+        ImageStructure::read(self, directory)
+    }
+
+    // Which raw camera format this is, if it's one we know. See RawFormat::detect.
+    pub fn raw_format(&self) -> Option<RawFormat> {
+        RawFormat::detect(self)
+    }
+
+    // The extra header a Canon CR2 has after its tiff header, if this is one.
+    pub fn cr2_header(&self) -> Option<Cr2Header> {
+        match self.file_type {
+            FileType::Cr2 => Cr2Header::parse(self.tiff(), self.header.byte_order),
+            _ => None
+        }
+    }
+
+    // When the photo was taken (or changed, or digitized), put together from its date, sub-second
+    // and offset tags. Ok(None) if the file doesn't say.
+    pub fn date_time(&self, kind: DateTimeKind) -> Result<Option<ExifDateTime>> {
+        ExifDateTime::read(self, kind)
+    }
+
+    // Where the photo was taken, in decimal degrees, from the GPS IFD. Ok(None) if there isn't one.
+    pub fn gps(&self) -> Result<Option<GpsInfo>> {
+        GpsInfo::read(self)
+    }
+
+    // Where on Earth the image is, for GeoTIFFs. Ok(None) for everything else.
+    pub fn geotiff(&self) -> Result<Option<GeoTiff>> {
+        GeoTiff::read(self)
+    }
+
+    // The lens, firmware, shooting mode and serial number from a Canon's maker note. Ok(None) for
+    // every other make.
+    pub fn canon_maker_note(&self) -> Result<Option<CanonMakerNote>> {
+        CanonMakerNote::read(self)
+    }
+
+    // Set a tag's value in the directory the spec puts it in (IFD0, for tags we don't know),
+    // replacing whatever was there, and creating the Exif or GPS IFD if it needs one that isn't
+    // there. The change is made to our copy of the file straight away, so it reads back like
+    // anything else, and save writes it out. See write.rs for how. Works on jpegs and tiffs.
+    pub fn set<'v, V: Into<TagValue<'v>>>(&mut self, tag: TagId, value: V) -> Result<()> {
+        let kind = tag.home().unwrap_or(IfdKind::Ifd(0));
+        self.edit(&[Edit::Set(kind, tag, value.into().into_owned())])
+    }
+
+    // Write the file, with any changes, to path.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.whole_file()?)?;
+        Ok(())
+    }
+
+    // Remove a tag from the directory it belongs in (IFD0, for tags we don't know). Removing
+    // GPSInfoIFDPointer removes all the GPS tags.
+    pub fn remove_tag(&mut self, tag: TagId) -> Result<()> {
+        let kind = tag.home().unwrap_or(IfdKind::Ifd(0));
+        self.edit(&[Edit::Remove(kind, tag)])
+    }
+
+    // Remove the Exif, GPS or Interop directory, and everything in it.
+    pub fn remove_directory(&mut self, kind: IfdKind) -> Result<()> {
+        self.edit(&[Edit::RemoveDirectory(kind)])
+    }
+
+    // Layer other files' metadata over this one's, in order, so where they disagree the last one
+    // wins. Tags that only make sense in the file they came from, like the image size and maker
+    // notes, aren't copied. See copy_edits.
+    pub fn merge(&mut self, overlays: &[&Exif]) -> Result<()> {
+        let edits: Vec<Edit> = overlays.iter().flat_map(|overlay| copy_edits(overlay)).collect();
+        self.edit(&edits)
+    }
+
+    // Make some changes, all at once, and read the result back in, so everything's where the new
+    // file says it is. Nothing is changed if any of them can't be made. See write.rs.
+    pub fn edit(&mut self, edits: &[Edit]) -> Result<()> {
+        // Edits are spliced into the file, so we need all of it.
+        if self.rest.is_some() {
+            let buf = self.whole_file()?.into_owned();
+            let duplicates = self.duplicates;
+            *self = Exif::from_bytes_with(&buf, self.mode)?;
+            self.duplicates = duplicates;
+        }
+        let buf = rewrite(self, edits)?;
+        let duplicates = self.duplicates;
+        *self = Exif::from_bytes_with(&buf, self.mode)?;
+        self.duplicates = duplicates;
+        Ok(())
+    }
+
+    // An entry's value as text. See IFD::value_string.
+    pub fn value_string(&self, entry: &IFD) -> Result<String> {
+        entry.value_string(self.tiff(), self.header.byte_order)
+    }
+
+    // The whole file, reading the rest of it if we only read the start. If it's changed since then,
+    // what we parsed doesn't describe it any more, so that's an error.
+    fn whole_file(&self) -> Result<Cow<'_, [u8]>> {
+        let path = match &self.rest {
+            Some(path) => path,
+            None => return Ok(Cow::Borrowed(&self.buf))
+        };
+        let buf = fs::read(path)?;
+        if let Ok(mut stats) = self.read_stats.lock() {
+            if let Some(stats) = stats.as_mut() {
+                stats.misses += 1;
+                stats.bytes_read += buf.len() as u64;
+            }
+        }
+        if !buf.starts_with(&self.buf) {
+            let e = io::Error::new(io::ErrorKind::InvalidData, "The file has changed since its exif was read.");
+            return Err(ExifError::Io(e));
+        }
+        Ok(Cow::Owned(buf))
+    }
+}
+
+// Follow the pointer tags down from IFD0. Each sub-directory is looked for in the directory the spec
+// puts its pointer in, and we recurse into whatever we find, in case it has pointers of its own.
+fn read_sub_ifds(tiff: &[u8], parent: &Directory, header: &TiffHeader, mode: ParseMode) -> Result<Vec<Directory>> {
+    let pointers: &[(TagId, IfdKind)] = match parent.kind {
+        IfdKind::Ifd(0) => &[(TagId::ExifIFDPointer, IfdKind::Exif), (TagId::GPSInfoIFDPointer, IfdKind::Gps)],
+        IfdKind::Exif => &[(TagId::InteroperabilityIFDPointer, IfdKind::Interop)],
+        _ => &[],
+    };
+    let mut found = Vec::new();
+    for &(tag, kind) in pointers {
+        if let Some(directory) = parent.read_sub_ifd(tiff, tag.id(), kind, header.byte_order, header.variant, mode)? {
+            let children = read_sub_ifds(tiff, &directory, header, mode)?;
+            found.push(directory);
+            found.extend(children);
+        }
+    }
+    if parent.kind == IfdKind::Ifd(0) {
+        found.extend(read_subifds_tag(tiff, parent, header, mode)?);
+    }
+    Ok(found)
+}
+
+// The SubIFDs tag is a list of offsets rather than a single one, so it needs decoding like any
+// other value. Like the other pointers, a broken one is skipped unless we're being strict.
+fn read_subifds_tag(tiff: &[u8], parent: &Directory, header: &TiffHeader, mode: ParseMode) -> Result<Vec<Directory>> {
+    let entry = match parent.entries.iter().find(|entry| entry.tag == TagId::SubIFDs.id()) {
+        Some(entry) => entry,
+        None => return Ok(Vec::new())
+    };
+    let offsets = match TagValue::decode(entry, tiff, header.byte_order).map(|value| value.to_u64s()) {
+        Ok(Some(offsets)) => offsets,
+        Ok(None) if mode.is_strict() => return Err(ExifError::UnsupportedType(entry.tag_type)),
+        Err(e) if mode.is_strict() => return Err(e),
+        _ => Vec::new()
+    };
+    let mut found = Vec::new();
+    for (i, offset) in offsets.into_iter().enumerate() {
+        let directory = to_usize(offset, "SubIFD")
+            .and_then(|offset| Directory::read(tiff, offset, IfdKind::SubIfd(i), header.byte_order, header.variant, mode));
+        match directory {
+            Ok(directory) => found.push(directory),
+            Err(e) if mode.is_strict() => return Err(e),
+            Err(_) => {}
+        }
+    }
+    Ok(found)
+}
+
+// String pointer into characters, into char vector, into an iterator of 2 character pairs,
+// which we concat, and radix parse into a 16 bit value. There's probably an easier way to
+// accomplish this. 🤔
+fn bytes_from_str(s: &str) -> Vec<u8> {
+    s.chars().collect::<Vec<char>>().chunks(2).map(|chars| {
+        let mut byte = String::new();
+        byte.push(chars[0]);
+        if chars.len() > 1 {
+            byte.push(chars[1]);
+        } else {
+            byte.push('0');
+        }
+        u8::from_str_radix(&byte, 16).unwrap_or(0)
+    }).collect()
+}
+
+// Find the offset of a byte sequence. Inelegantly implemented, since our unwrap default value must
+// match the type of the closure parameters (usize, &[u]). The first parameter comes from
+// enumerate(), which is a usize, the second comes from windows(...), which returns seq.len()
+// slices. We ignore the index value in the actual closure because we don't need it.
+fn find(buf: &[u8], seq: &[u8]) -> usize {
+    buf.windows(seq.len()).enumerate().find(|(_, bytes)| {
+        // Here we're dealing with a window of size seq.len() of buf, matched against seq.
+        // Windows are created above, so we simply zip bytes, and seq iterators together, and then
+        // reduce each pair to a boolean. Unrolled: ((a[0] == b[0]) && (a[1] == b[1]) && (a[2] == b[2]) ...n)
+        bytes.iter().zip(seq.iter()).fold(true, |acc, (a, b)| acc && a == b)
+    }).unwrap_or((0, &[0])).0
+}
+
+// Look for either tiff header signature, and take whichever comes first. find() hands back 0 when
+// it doesn't find anything, which is why we skip those. A tiff header can't start at byte 0 of a
+// jpeg anyway.
+fn find_tiff_header(buf: &[u8]) -> Option<usize> {
+    ["49492a00", "4d4d002a"].iter()
+        .map(|marker| find(buf, &bytes_from_str(marker)))
+        .filter(|&offset| offset != 0)
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &[&[u8]] = &[
+        include_bytes!("../fixtures/le.tif"),
+        include_bytes!("../fixtures/be.tif"),
+        include_bytes!("../fixtures/bigtiff.tif"),
+        include_bytes!("../fixtures/bigtiff-be.tif"),
+        include_bytes!("../fixtures/exif.jpg"),
+        include_bytes!("../fixtures/exif.png"),
+        include_bytes!("../fixtures/exif.heic"),
+        include_bytes!("../fixtures/truncated.jpg"),
+    ];
+
+    // Read everything we know how to read out of buf. Errors are fine, panics aren't.
+    fn read_everything(buf: &[u8]) {
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let exif = match Exif::from_bytes_with(buf, mode) {
+                Ok(exif) => exif,
+                Err(_) => continue
+            };
+            for directory in exif.directories() {
+                for entry in &directory.entries {
+                    let _ = exif.value(entry).map(|value| value.to_string());
+                    let _ = exif.value_string(entry);
+                }
+                let _ = exif.image_structure(directory);
+            }
+            for kind in [DateTimeKind::Original, DateTimeKind::Digitized, DateTimeKind::Modified] {
+                let _ = exif.date_time(kind);
+            }
+            let _ = (exif.gps(), exif.geotiff(), exif.canon_maker_note(), exif.thumbnail(), exif.image_data());
+        }
+        let _ = identify(buf);
+    }
+
+    // Every fixture cut short at every length, and with every byte changed to a few values that
+    // make for interesting lengths and offsets.
+    #[test]
+    fn mangled_fixtures_do_not_panic() {
+        for fixture in FIXTURES {
+            for length in 0..fixture.len() {
+                read_everything(&fixture[..length]);
+            }
+            for i in 0..fixture.len() {
+                for byte in [0x00, 0x01, 0x7f, 0x80, 0xfe, 0xff] {
+                    let mut mangled = fixture.to_vec();
+                    mangled[i] = byte;
+                    read_everything(&mangled);
+                }
+            }
+        }
+    }
+}
@@ -1,11 +1,18 @@
-use std::{io, io::prelude::*};
-use std::fs::File;
+use std::env;
+use std::ffi::OsString;
 use std::fmt;
-use std::str;
-use std::panic;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+use exif::{describe, BugReport, ByteOrder, CanonMakerNote, CarvedJpeg, Carver, DateTimeKind, Duplicates, Edit, Exif, ExifDateTime, ExifError, FileType, GeoTiff, IfdKind, ImageData, ImageStructure, Layout, ParseMode, strip_metadata, TagId, TagValue, TiffVariant, to_usize};
 
 // Utility function to turn format a Vec<u8> into a LowerHex formatted String repr.
-fn byte_vec_to_hex_string(v: &Vec<u8>) -> String {
+fn byte_vec_to_hex_string(v: &[u8]) -> String {
     v.iter().map(|value| format!("{:02x} ", value)).collect::<String>()
 }
 
@@ -29,211 +36,1503 @@ impl fmt::LowerHex for ByteSlice {
     }
 }
 
-// Image File Directory, source: https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf
-// https://www.exif.org/Exif2-2.PDF, 4.6.2 IFD Structure
+// Print a random offset as bytes. There's no display trait for lower hex values for &[u8] (byte
+// slices), so we wrap our slice in a tuple type, and then we impl fmt::LowerHex on that tuple
+// type. We'd implement fmt::LowerHex right on &[u8], but slices are defined outside this crate.
+// Not being able to arbitrarily extend the standard library in your crate is deliberate.
+// If the range runs off the end of buf (or past the end of memory) we print as much of it as
+// there is. Bytes in any of the hidden ranges (see Redact) print as xx instead.
+fn print_offset(buf: &[u8], offset: usize, length: usize, hidden: &[Range<usize>]) {
+    let end = offset.checked_add(length).map_or(buf.len(), |end| end.min(buf.len()));
+    let bytes = buf.get(offset..end).unwrap_or(&[]);
+    if !hidden.iter().any(|range| range.start < end && offset < range.end) {
+        println!("{:02x}", ByteSlice(bytes.to_vec()));
+        return;
+    }
+    let repr = bytes.iter().enumerate().map(|(i, value)| {
+        if hidden.iter().any(|range| range.contains(&(offset + i))) {
+            "xx ".to_string()
+        } else {
+            format!("{:02x} ", value)
+        }
+    }).collect::<String>();
+    println!("{}", repr);
+}
+
+const USAGE: &str = "Usage: helloexif [--strict | --lenient] [--duplicates first|last|all] [--json-raw] [--redact WHAT]
+                 [--extract-thumbnail OUT] [--bug-report OUT] [--include PATTERNS] [--timing]
+                 [--check-image-data] FILE [FILE...]
+       helloexif carve IMAGE --out DIR
+       helloexif --strip FILE -o OUT
+       helloexif minimize FILE --out OUT
+       helloexif --remove TAGS FILE -o OUT
+       helloexif identify FILE [FILE...]
+       helloexif anomalies DIR [--include PATTERNS]
+       helloexif sessions DIR [--gap TIME] [--include PATTERNS]
+       helloexif merge --base FILE --overlay FILE [--overlay FILE...] --out OUT
+       helloexif selftest
+
+Prints the tiff header, the entries in each IFD, and the Make exif tag, for each FILE. A FILE can
+be a directory, which means every image in it, and in the directories in it, and so on, with a
+count of how many we could read at the end.
+
+--strict   Treat any spec violation as an error, for checking files rather than reading them.
+--lenient  Read what we can, and step around what we can't. This is the default.
+--duplicates first|last|all
+           Which value to use when a tag appears more than once in a directory. all reports every
+           one of them. The default is first.
+--json-raw Print the structure we found instead: where every IFD and entry is, their raw bytes, and
+           where each value is, as one line of JSON per FILE.
+--extract-thumbnail OUT
+           Write the thumbnail jpeg embedded in IFD1 to OUT. Takes one FILE, and not with --json-raw.
+--bug-report OUT
+           If FILE can't be read, write a tar to OUT with its metadata (never its image data) and
+           what we found on the way through it, for attaching to a bug report. Takes one FILE.
+--include PATTERNS
+           Which files in directories count as images: a comma separated list of names, where * matches
+           anything, like '*.jpg,*.cr2'. The default is every extension we know. Files named outright
+           are always read.
+--redact gps,serial,owner
+           Hide these values in what we print, for sharing output without sharing what's in the
+           photo. gps is the whole GPS IFD, serial is camera and lens serial numbers (a Canon's
+           maker note's included) and the image ID, and owner is the camera owner's and artist's
+           names. The file isn't changed.
+--check-image-data
+           Walk each jpeg's image data to check it's all there, and warn if the file ends partway
+           through the picture. This reads the whole file, where otherwise we only read the start.
+--timing   Say on stderr how long reading each FILE took, how many bytes of it we read, and how
+           many of the reads came out of what we'd already read (hits) or had to go to the file
+           (misses).
+
+carve    Recovers jpegs from a raw disk or memory card IMAGE into DIR, named by when they were taken.
+--strip  Writes a copy of the jpeg FILE to OUT without its metadata: exif (GPS included), XMP, ICC
+         profiles, maker notes, comments. The image data is copied exactly, and anything after it,
+         like a second image, is left out.
+--remove Writes a copy of FILE to OUT without the tags named in TAGS, a comma separated list of
+         names, where * matches anything. GPS* takes out all the location data. The rest of the
+         file is left alone.
+identify Guesses what camera (or program) each FILE came from, from more than its Make and Model
+         tags, which may have been taken out. It says why, and it's only a guess.
+anomalies
+         Works out what's normal for the photos in DIR (and the directories in it): the camera,
+         the software, whether there are maker notes, the time zone. Then lists the ones that
+         aren't, and the places the time zone changes from one photo to the next. Different isn't
+         necessarily wrong, but it's worth a look.
+sessions Sorts the photos in DIR (and the directories in it) by when they were taken, and splits
+         them into shooting sessions wherever there's more than TIME between two of them, like 2h,
+         30m or 1d. The default is 2h. Times are the camera's, so it's best with one camera.
+merge    Writes a copy of the --base FILE to OUT with the metadata from each --overlay FILE on top,
+         later ones winning. Tags about the picture itself, like its size, come from the base.
+         Overlays have to be images we can read exif from, for now, not XMP or JSON.
+minimize Writes a copy of the jpeg FILE to OUT with its metadata exactly as it was, but a single grey
+         pixel for a picture, for sharing files that break us as test cases.
+selftest Reads the tiny sample files built into the program, and says whether they came out right.";
+
+fn main() {
+    // Filenames are Paths rather than strs. A path is whatever bytes (unix) or u16s (windows) the
+    // OS handed us, which aren't guaranteed to be valid utf8, so we collect arguments with
+    // args_os() (plain args() panics on those), and only convert to text for printing, with
+    // display(), which swaps anything it can't represent for U+FFFD. Opening still uses the
+    // original OsStr, so odd names don't get skipped. On windows, std quietly rewrites paths longer
+    // than MAX_PATH into the \\?\ verbatim form for us when opening, so long paths work too.
+    //
+    // The first argument is the program itself, so we skip it.
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    if args.is_empty() {
+        usage();
+    }
+
+    // A few commands do something other than print files. Anything else is a file to print.
+    let ok = match args[0].to_str() {
+        Some("carve") => carve(&args[1..]),
+        Some("selftest") if args.len() == 1 => selftest(),
+        Some("--strip") => strip(&args[1..]),
+        Some("minimize") => minimize(&args[1..]),
+        Some("merge") => merge(&args[1..]),
+        Some("sessions") => sessions(&args[1..]),
+        Some("anomalies") => anomalies(&args[1..]),
+        Some("identify") if args.len() > 1 => identify(&args[1..]),
+        Some("--remove") if args.len() > 1 => remove(&args[1], &args[2..]),
+        _ => print_files(&args)
+    };
+    if !ok {
+        process::exit(1);
+    }
+}
+
+// Complain about how we were called, and give up.
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+// How to print files, from the flags we were given.
 #[derive(Default)]
-struct IFD {
-    tag: u16,
-    tag_type: u16,
-    count: u32,
-    value_offset: u32,
+struct Options {
+    mode: ParseMode,
+    duplicates: Duplicates,
+    json_raw: bool,
+    redact: Vec<Redact>,
+    thumbnail: Option<PathBuf>,
+    bug_report: Option<PathBuf>,
+    include: Vec<String>,
+    timing: bool,
+    check_image_data: bool,
 }
 
-// From for IFD. This enables IFD::from(&[u8]) (an IFD struct from a u8 slice)
-impl From<&[u8]> for IFD {
-    fn from(v: &[u8]) -> Self {
-        // [u8; 2] is an array comprised of u8 values, here assigned a length 2 array filled with 0s.
-        // This section is a bit kludgey, since it'd be cool to be able to instantiate an array
-        // from an arbitrary slice. Arrays are typed over some contiguous type and a length, and
-        // since slices are arbitrary over the length of a program (&v[..1], &v[2..e], etc), I can
-        // see why we want to be careful.
-        let mut tag_bytes: [u8; 2] = [0; 2];
-        tag_bytes.copy_from_slice(&v[..2]);
+// Values --redact can hide. They're still read, they just aren't shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Redact {
+    Gps,
+    Serial,
+    Owner,
+}
 
-        let mut type_bytes: [u8; 2] = [0; 2];
-        type_bytes.copy_from_slice(&v[2..4]);
+impl Redact {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gps" => Some(Redact::Gps),
+            "serial" => Some(Redact::Serial),
+            "owner" => Some(Redact::Owner),
+            _ => None
+        }
+    }
 
-        let mut count_bytes: [u8; 4] = [0; 4];
-        count_bytes.copy_from_slice(&v[4..8]);
+    // Whether this hides an entry with this tag, in this kind of directory.
+    fn covers(&self, kind: IfdKind, tag: TagId) -> bool {
+        match self {
+            Redact::Gps => kind == IfdKind::Gps,
+            Redact::Serial => matches!(tag, TagId::BodySerialNumber | TagId::LensSerialNumber | TagId::ImageUniqueID),
+            Redact::Owner => matches!(tag, TagId::CameraOwnerName | TagId::Artist),
+        }
+    }
+}
 
-        let mut value_offset_bytes: [u8; 4] = [0; 4];
-        value_offset_bytes.copy_from_slice(&v[8..]);
+impl Options {
+    fn redacts(&self, kind: IfdKind, tag: TagId) -> bool {
+        self.redact.iter().any(|redact| redact.covers(kind, tag))
+    }
 
-        IFD {
-            tag: u16::from_le_bytes(tag_bytes),
-            tag_type: u16::from_le_bytes(type_bytes),
-            count: u32::from_le_bytes(count_bytes),
-            value_offset: u32::from_le_bytes(value_offset_bytes)
+    // Where the redacted values are in the file, so the hex dump can hide them too: the
+    // value_offset field of each redacted entry, since small values live there, and the value
+    // itself, if it's somewhere else. A value that runs off the end of what we can address is
+    // hidden to the end, and one that starts there can't be in the file to be hidden.
+    fn hidden_ranges(&self, exif: &Exif) -> Vec<Range<usize>> {
+        let base = exif.tiff_header_offset();
+        let variant = exif.variant();
+        let mut hidden = Vec::new();
+        for ifd in exif.directories() {
+            for (i, entry) in ifd.entries.iter().enumerate() {
+                if !self.redacts(ifd.kind, entry.tag_id(ifd.kind)) {
+                    continue;
+                }
+                let field = base + ifd.entry_offset(i, variant) + variant.entry_size() - variant.offset_size();
+                hidden.push(field..field + variant.offset_size());
+                if let (Some(offset), Some(length)) = (entry.data_offset(), entry.data_length()) {
+                    if let Some(start) = to_usize(offset, "value").ok().and_then(|offset| base.checked_add(offset)) {
+                        let end = to_usize(length, "value").ok().and_then(|length| start.checked_add(length));
+                        hidden.push(start..end.unwrap_or(usize::MAX));
+                    }
+                }
+            }
         }
+        hidden
     }
 }
 
-// LowerHex formatter for our IFD struct.
-// We implement this formatter so that we can print out this struct with println!("{:x}", ifd);
-impl fmt::LowerHex for IFD {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let tag = self.tag.to_le_bytes();
-        let tag_type = self.tag_type.to_le_bytes();
-        let count = self.count.to_le_bytes();
-        let value_offset = self.value_offset.to_le_bytes();
+// Print every file we were given. Try every file, even if an earlier one failed, but let the
+// caller know something went wrong. Flags can go anywhere, and apply to every file.
+fn print_files(args: &[OsString]) -> bool {
+    let mut options = Options::default();
+    let mut filenames = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--strict") => options.mode = ParseMode::Strict,
+            Some("--lenient") => options.mode = ParseMode::Lenient,
+            Some("--duplicates") => {
+                options.duplicates = match args.next().and_then(|policy| policy.to_str()) {
+                    Some("first") => Duplicates::First,
+                    Some("last") => Duplicates::Last,
+                    Some("all") => Duplicates::All,
+                    _ => usage()
+                }
+            },
+            Some("--json-raw") => options.json_raw = true,
+            Some("--timing") => options.timing = true,
+            Some("--check-image-data") => options.check_image_data = true,
+            Some("--extract-thumbnail") => options.thumbnail = Some(args.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            Some("--bug-report") => options.bug_report = Some(args.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            Some("--include") => {
+                let patterns = args.next().and_then(|patterns| patterns.to_str()).unwrap_or_else(|| usage());
+                options.include.extend(patterns.split(',').map(|pattern| pattern.trim().to_string()));
+            },
+            Some("--redact") => {
+                let names = args.next().and_then(|names| names.to_str()).unwrap_or_else(|| usage());
+                for name in names.split(',') {
+                    options.redact.push(Redact::from_name(name.trim()).unwrap_or_else(|| usage()));
+                }
+            },
+            Some(flag) if flag.starts_with("--") => usage(),
+            _ => filenames.push(PathBuf::from(arg))
+        }
+    }
+    if filenames.is_empty() {
+        usage();
+    }
 
-        // :02 is format width, so we print '0f' instead of just 'f'
-        let fields = format!("{:02x?}{:02x?}{:02x?}{:02x?}", tag, tag_type, count, value_offset);
-        write!(f, "{}", fields)
+    // Swap directories for the images in them.
+    let mut ok = true;
+    let walked = filenames.iter().any(|filename| filename.is_dir());
+    let mut files = Vec::new();
+    for filename in filenames {
+        if filename.is_dir() {
+            ok &= walk(&filename, &options.include, &mut files);
+        } else {
+            files.push(filename);
+        }
+    }
+    let single = options.thumbnail.is_some() || options.bug_report.is_some();
+    if (single && files.len() != 1) || (options.thumbnail.is_some() && options.json_raw) {
+        usage();
     }
+
+    let mut failed = Vec::new();
+    for (i, filename) in files.iter().enumerate() {
+        let read = if options.json_raw {
+            print_json_raw(filename, &options)
+        } else {
+            if i > 0 {
+                println!();
+            }
+            print_file(filename, &options)
+        };
+        if !read {
+            failed.push(filename);
+        }
+    }
+
+    // With a directory, there can be more files than anyone wants to scroll back through, so sum
+    // up. That goes to stderr with --json-raw, to keep stdout all JSON.
+    if walked {
+        let mut summary = format!("Read {} of {} files.", files.len() - failed.len(), files.len());
+        if !failed.is_empty() {
+            summary.push_str(" Couldn't read:");
+            for filename in &failed {
+                summary.push_str(&format!("\n  {}", filename.display()));
+            }
+        }
+        if options.json_raw {
+            eprintln!("{}", summary);
+        } else {
+            println!();
+            println!("{}", summary);
+        }
+    }
+    ok && failed.is_empty()
 }
 
-// Default formatter for our IFD struct.
-// We implement this formatter so that we can print out this struct with println!("{}", ifd);
-impl fmt::Display for IFD {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let fields = format!("{}, {}, {}, {}", self.tag, self.tag_type, self.count, self.value_offset);
-        write!(f, "{}", fields)
+// Collect the images in a directory, and in the directories in it, in name order. Images are the
+// files matching one of the include patterns, or, without any, with an extension we know. We don't
+// follow symlinks to directories, since they can go round in circles. Returns false if there was a
+// directory we couldn't read, after carrying on without it.
+fn walk(dir: &Path, include: &[String], files: &mut Vec<PathBuf>) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Couldn't read the directory {}: {}", dir.display(), e);
+            return false
+        }
+    };
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    let mut ok = true;
+    for entry in entries {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false);
+        if is_dir {
+            ok &= walk(&path, include, files);
+        } else if is_image(&path, include) {
+            files.push(path);
+        }
     }
+    ok
 }
 
-// Methods on struct IFD.
-impl IFD {
-    // Basically a constructor (takes no `self` parameter, and returns a Self)
-    // This wraps the From trait above. Technically we can just do this wherever we wanted to
-    // generate an IFD, but I decided parameterizing from over from_offset was a nice to have.
-    fn from_offset(buf: &Vec<u8>, offset: usize) -> Self {
-        IFD::from(&buf[offset..offset+12])
+fn is_image(path: &Path, include: &[String]) -> bool {
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return false
+    };
+    if !include.is_empty() {
+        return include.iter().any(|pattern| glob_match(pattern, &name));
     }
+    let extension = match path.extension() {
+        Some(extension) => extension.to_string_lossy().to_lowercase(),
+        None => return false
+    };
+    [FileType::Jpeg, FileType::Png, FileType::Tiff, FileType::Cr2, FileType::Heif].iter()
+        .any(|file_type| file_type.extensions().contains(&extension.as_str()))
+}
 
-    // Takes buf, tiff header offset, since we may need to actually go get that value from some
-    // other location (as designated from value_offset). "May", because according to the spec, if
-    // the value of value_offset (latter 4 bytes in this slice) takes up less than or equal to the
-    // 4 bytes for this field, the value itself will be inserted. We don't do that check here for
-    // brevity.
-    //
-    // Also, we perform a panic catch here because we might be trying to read a random byte
-    // offset as a utf8 string, and that offset might be expressed in the wrong endianness, and go
-    // over the length of the byte buffer. I... guess this'd be a segfault in C? Traipsing off the
-    // far end of a heap allocated byte buffer because your endianness was wrong? idk.
-    fn print_value(&self, buf: &Vec<u8>, header_offset: usize) {
-        match panic::catch_unwind(|| {
-            print_offset_as_string(
-                buf,
-                header_offset + self.value_offset as usize,
-                self.count as usize
-            );
-        }) {
-            Ok(_) => {},
-            Err(_) => {
-                println!("Caught panic while printing value -- values may have been stored in other endianness.");
+// Quote and escape a string for JSON. Control characters other than the usual few get \u escapes.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// An optional number, or null.
+fn json_number(n: Option<u64>) -> String {
+    n.map(|n| n.to_string()).unwrap_or_else(|| "null".to_string())
+}
+
+// The structural view of a file, as one line of JSON, for tools that want to build on what we
+// found rather than scrape our output. Offsets come in pairs: relative to the tiff header (what
+// the file itself says), and from the start of the file (where you'd seek to). data_offset is null
+// when the value is stored inline, in the entry's own value_offset bytes.
+//
+// We write the JSON by hand, since it's simple, and it saves a dependency.
+fn print_json_raw(filename: &Path, options: &Options) -> bool {
+    let name = json_string(&filename.display().to_string());
+    let started = Instant::now();
+    let mut exif = match read_exif(filename, options, started) {
+        Ok(exif) => exif,
+        Err(e) => {
+            println!("{{\"file\":{},\"error\":{}}}", name, json_string(&e.to_string()));
+            write_bug_report(filename, options, &e);
+            return false
+        }
+    };
+    exif.set_duplicates(options.duplicates);
+    let base = exif.tiff_header_offset();
+    let variant = exif.variant();
+    let byte_order = match exif.byte_order() {
+        ByteOrder::LittleEndian => "II",
+        ByteOrder::BigEndian => "MM",
+    };
+
+    let ifds = exif.directories().map(|ifd| {
+        let entries = ifd.entries.iter().enumerate().map(|(i, entry)| {
+            let offset = ifd.entry_offset(i, variant);
+            let redacted = options.redacts(ifd.kind, entry.tag_id(ifd.kind));
+            // A redacted entry's value_offset (and so its raw bytes) can be the value itself.
+            let (value_offset, raw) = if redacted {
+                (None, "null".to_string())
+            } else {
+                let raw = exif.tiff().get(offset..offset + variant.entry_size()).unwrap_or(&[]);
+                (Some(entry.value_offset), format!("\"{}\"", raw.iter().map(|b| format!("{:02x}", b)).collect::<String>()))
+            };
+            let data_offset = entry.data_offset();
+            format!(
+                "{{\"tag\":{},\"name\":{},\"type\":{},\"count\":{},\"value_offset\":{},\"raw\":{},\"offset\":{},\"file_offset\":{},\"data_offset\":{},\"data_file_offset\":{},\"data_length\":{},\"redacted\":{}}}",
+                entry.tag, json_string(&entry.tag_id(ifd.kind).to_string()), entry.tag_type, entry.count,
+                json_number(value_offset), raw, offset, base + offset, json_number(data_offset),
+                json_number(data_offset.map(|offset| offset + base as u64)), json_number(entry.data_length()), redacted
+            )
+        }).collect::<Vec<String>>();
+        format!(
+            "{{\"kind\":{},\"byte_order\":\"{}\",\"offset\":{},\"file_offset\":{},\"entry_count\":{},\"next_offset\":{},\"entries\":[{}]}}",
+            json_string(&ifd.kind.to_string()), byte_order, ifd.offset, base + ifd.offset, ifd.entries.len(),
+            ifd.next_offset, entries.join(",")
+        )
+    }).collect::<Vec<String>>();
+
+    let variant = match variant {
+        TiffVariant::Classic => "tiff",
+        TiffVariant::Big => "bigtiff",
+    };
+    println!(
+        "{{\"file\":{},\"file_type\":{},\"tiff_header_offset\":{},\"byte_order\":\"{}\",\"variant\":\"{}\",\"ifd0_offset\":{},\"ifds\":[{}]}}",
+        name, json_string(&exif.file_type().to_string()), base, byte_order, variant, exif.ifd0_offset(), ifds.join(",")
+    );
+    print_timing(filename, options, started, &exif);
+    true
+}
+
+// helloexif carve IMAGE --out DIR
+fn carve(args: &[OsString]) -> bool {
+    let (image, out) = file_and_out(args, "--out");
+
+    let file = match File::open(&image) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("An error occurred while trying to open {}: {}", image.display(), e);
+            return false
+        }
+    };
+    if let Err(e) = fs::create_dir_all(&out) {
+        eprintln!("Couldn't create {}: {}", out.display(), e);
+        return false
+    }
+
+    // BufReader, since the carver asks for its data in a lot of little reads.
+    let mut found = 0;
+    for jpeg in Carver::new(BufReader::new(file)) {
+        let jpeg = match jpeg {
+            Ok(jpeg) => jpeg,
+            Err(e) => {
+                eprintln!("Error while reading {}: {}", image.display(), e);
+                return false
             }
         };
+        let path = unused_path(&out, &carved_name(&jpeg));
+        if let Err(e) = fs::write(&path, &jpeg.data) {
+            eprintln!("Couldn't write {}: {}", path.display(), e);
+            return false
+        }
+        println!("{:#010x}: {} bytes -> {}", jpeg.offset, jpeg.data.len(), path.display());
+        found += 1;
     }
+    println!("Recovered {} jpegs.", found);
+    true
 }
 
-// Print a random offset as bytes. There's no display trait for lower hex values for &[u8] (byte
-// slices), so we wrap our slice in a tuple type, and then we impl fmt::LowerHex on that tuple
-// type. We'd implement fmt::LowerHex right on &[u8], but slices are defined outside this crate.
-// Not being able to arbitrarily extend the standard library in your crate is deliberate.
-fn print_offset(buf: &Vec<u8>, offset: usize, length: usize) {
-    println!("{:02x}", ByteSlice(buf[offset..offset+length].to_vec()));
+// Write a copy of a jpeg without its metadata. We read the file ourselves rather than through Exif,
+// since a file with no exif (or broken exif) can still have other metadata worth removing, and
+// there's no size limit, since we're not parsing anything.
+fn strip(args: &[OsString]) -> bool {
+    let (file, out) = file_and_out(args, "-o");
+    let buf = match read_jpeg(&file) {
+        Some(buf) => buf,
+        None => return false
+    };
+    let stripped = match strip_metadata(&buf) {
+        Ok(stripped) => stripped,
+        Err(e) => {
+            eprintln!("Couldn't strip {}: {}", file.display(), e);
+            return false
+        }
+    };
+    if let Err(e) = fs::write(&out, &stripped) {
+        eprintln!("Couldn't write {}: {}", out.display(), e);
+        return false
+    }
+    println!("Removed {} bytes of metadata, and wrote {}.", buf.len() - stripped.len(), out.display());
+    true
 }
 
-// Try to utf8 parse a random byte offset. This can panic.
-fn print_offset_as_string(buf: &Vec<u8>, offset: usize, length: usize) {
-    match str::from_utf8(&buf[offset..offset+length]) {
-        Ok(s) => println!("'{}'", s),
-        Err(e) => println!("Error while printing range: {}", e)
+// Write a copy of a jpeg with its metadata but a placeholder for its picture, to share as a test
+// case. Like strip, this doesn't need to understand the exif to work.
+fn minimize(args: &[OsString]) -> bool {
+    let (file, out) = file_and_out(args, "--out");
+    let buf = match read_jpeg(&file) {
+        Some(buf) => buf,
+        None => return false
+    };
+    let minimized = match exif::minimize(&buf) {
+        Ok(minimized) => minimized,
+        Err(e) => {
+            eprintln!("Couldn't minimize {}: {}", file.display(), e);
+            return false
+        }
+    };
+    if let Err(e) = fs::write(&out, &minimized) {
+        eprintln!("Couldn't write {}: {}", out.display(), e);
+        return false
+    }
+    println!("Wrote {} ({} bytes, from {}).", out.display(), minimized.len(), buf.len());
+    // Not everything in the metadata is text.
+    if Exif::from_bytes(&minimized).is_ok_and(|exif| exif.thumbnail().is_some()) {
+        eprintln!("Warning: {}'s exif has a thumbnail of the photo in it, which is still there.", out.display());
     }
+    true
 }
 
-// The whole thing.
-fn read_all(mut file: &File) -> io::Result<Vec<u8>> {
-    let mut buf = Vec::new();
-    file.read_to_end(&mut buf)?;
-    Ok(buf)
+// Say where each file came from, as far as we can tell.
+fn identify(args: &[OsString]) -> bool {
+    let mut ok = true;
+    for (i, filename) in args.iter().map(Path::new).enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}:", filename.display());
+        let buf = match fs::read(filename) {
+            Ok(buf) => buf,
+            Err(e) => {
+                eprintln!("An error occurred while trying to open {}: {}", filename.display(), e);
+                ok = false;
+                continue
+            }
+        };
+        if let Some(camera) = Exif::from_bytes(&buf).ok().and_then(|exif| camera_name(&exif)) {
+            println!("  says it's from: {}", camera);
+        }
+        let clues = exif::identify(&buf);
+        if clues.is_empty() {
+            println!("  nothing we recognise");
+        }
+        for clue in clues {
+            println!("  maybe {}: {}", clue.source, clue.reason);
+        }
+    }
+    ok
 }
 
-// String pointer into characters, into char vector, into an iterator of 2 character pairs,
-// which we concat, and radix parse into a 16 bit value. There's probably an easier way to
-// accomplish this. 🤔
-fn bytes_from_str(s: &str) -> Vec<u8> {
-    s.chars().collect::<Vec<char>>().chunks(2).map(|chars| {
-        let mut byte = String::new();
-        byte.push(chars[0]);
-        if chars.len() > 1 {
-            byte.push(chars[1]);
-        } else {
-            byte.push('0');
+// What anomalies compares between photos.
+struct Profile {
+    path: PathBuf,
+    camera: Option<String>,
+    software: Option<String>,
+    maker_note: bool,
+    taken: Option<ExifDateTime>,
+}
+
+impl Profile {
+    fn read(path: PathBuf, exif: &Exif) -> Self {
+        let software = match exif.entry(TagId::Software).map(|entry| exif.value(entry)) {
+            Some(Ok(TagValue::Ascii(software))) if !software.trim().is_empty() => Some(software.trim().to_string()),
+            _ => None
+        };
+        Profile {
+            path,
+            camera: camera_name(exif),
+            software,
+            maker_note: exif.entry(TagId::MakerNote).is_some(),
+            taken: exif.date_time(DateTimeKind::Original).ok().flatten(),
+        }
+    }
+
+    // The offset from UTC the photo was taken at, if the camera wrote one.
+    fn time_zone(&self) -> Option<String> {
+        self.taken?.offset.map(format_offset)
+    }
+
+    // What we compare, by name, as text, so they can all be compared the same way.
+    fn features(&self) -> [(&'static str, String); 4] {
+        let describe = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+        [
+            ("camera", describe(self.camera.clone())),
+            ("software", describe(self.software.clone())),
+            ("maker notes", if self.maker_note { "yes" } else { "no" }.to_string()),
+            ("time zone", describe(self.time_zone())),
+        ]
+    }
+}
+
+// Flag the photos in a directory that don't look like the rest.
+fn anomalies(args: &[OsString]) -> bool {
+    let mut dir = None;
+    let mut include = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--include") => {
+                let patterns = args.next().and_then(|patterns| patterns.to_str()).unwrap_or_else(|| usage());
+                include.extend(patterns.split(',').map(|pattern| pattern.trim().to_string()));
+            },
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => usage()
+        }
+    }
+    let dir = dir.unwrap_or_else(|| usage());
+    let mut files = Vec::new();
+    let mut ok = walk(&dir, &include, &mut files);
+    let mut profiles = Vec::new();
+    for path in files {
+        match Exif::from_path(&path) {
+            Ok(exif) => profiles.push(Profile::read(path, &exif)),
+            Err(e) => {
+                println!("{}: no readable exif, which is an anomaly in itself ({})", path.display(), e);
+                ok = false;
+            }
+        }
+    }
+    if profiles.is_empty() {
+        return ok;
+    }
+
+    let features: Vec<[(&str, String); 4]> = profiles.iter().map(Profile::features).collect();
+    let normal = normal_features(&features);
+    println!("Normal for these {} photos:", profiles.len());
+    for ((name, _), normal) in features[0].iter().zip(&normal) {
+        match normal {
+            Some((value, count)) => println!("  {}: {} ({} of {})", name, value, count, profiles.len()),
+            None => println!("  {}: nothing usual, they're all over the place", name)
+        }
+    }
+
+    println!("Anomalies:");
+    let mut found = 0;
+    for (profile, features) in profiles.iter().zip(&features) {
+        let reasons = differences(features, &normal);
+        if !reasons.is_empty() {
+            println!("  {}: {}", profile.path.display(), reasons.join("; "));
+            found += 1;
         }
-        u8::from_str_radix(&byte, 16).unwrap_or(0)
+    }
+    for (before, after) in time_zone_jumps(&profiles) {
+        println!("  {}: time zone jumps from {} to {} since {}", after.path.display(),
+            before.time_zone().unwrap_or_default(), after.time_zone().unwrap_or_default(), before.path.display());
+        found += 1;
+    }
+    if found == 0 {
+        println!("  none");
+    }
+    ok
+}
+
+// What's normal is what most of the photos have, and how many do. Where there's no majority,
+// there's no normal, and nothing to stand out from.
+fn normal_features(features: &[[(&'static str, String); 4]]) -> Vec<Option<(String, usize)>> {
+    (0..4).map(|i| {
+        let values: Vec<String> = features.iter().map(|features| features[i].1.clone()).collect();
+        most_common(&values).filter(|(_, count)| count * 2 > values.len())
+    }).collect()
+}
+
+// How one photo differs from the normal ones, in words.
+fn differences(features: &[(&'static str, String); 4], normal: &[Option<(String, usize)>]) -> Vec<String> {
+    features.iter().zip(normal).filter_map(|((name, value), normal)| match normal {
+        Some((normal, _)) if value != normal => Some(format!("{} is {} (usually {})", name, value, normal)),
+        _ => None
     }).collect()
 }
 
-// Find the offset of a byte sequence. Inelegantly implemented, since our unwrap default value must
-// match the type of the closure parameters (usize, &[u]). The first parameter comes from
-// enumerate(), which is a usize, the second comes from windows(...), which returns seq.len()
-// slices. We ignore the index value in the actual closure because we don't need it.
-fn find(buf: &Vec<u8>, seq: &Vec<u8>) -> usize {
-    buf.windows(seq.len()).enumerate().find(|(_, bytes)| {
-        // Here we're dealing with a window of size seq.len() of buf, matched against seq.
-        // Windows are created above, so we simply zip bytes, and seq iterators together, and then
-        // reduce each pair to a boolean. Unrolled: ((a[0] == b[0]) && (a[1] == b[1]) && (a[2] == b[2]) ...n)
-        bytes.iter().zip(seq.iter()).fold(true, |acc, (a, b)| acc && a == b)
-    }).unwrap_or((0, &[0])).0
-}
-
-fn main() -> io::Result<()> {
-    // These files should be included in the repository. They come from: https://github.com/ianare/exif-samples/tree/master/jpg
-    //let filename = "corrupted.jpg";
-    //let filename = "a.jpg";
-    //let filename = "Canon_40D.jpg";
-    let filename = "Kodak_CX7530.jpg";
-    println!("Reading file: {}", filename);
-    match File::open(filename) { // succeeds
-        Ok(file) => {
-            // Lets get some file stats
-            let md = file.metadata()?;
-            // Lets keep our images small so we can load them into memory
-            if md.len() > (1024 * 1000) {
-                eprintln!("This bin wasn't designed to handle files over 1mb!");
-                return Ok(())
+// In the order they were taken, the photos where the time zone changes, and the ones before them.
+// Travel does that, but so does stitching together photos from different places and passing them
+// off as one shoot. Like sessions, this goes by the camera's clock, since that's the one time all
+// of them have, offset or not.
+fn time_zone_jumps(profiles: &[Profile]) -> Vec<(&Profile, &Profile)> {
+    let mut dated: Vec<&Profile> = profiles.iter().filter(|profile| profile.taken.is_some()).collect();
+    dated.sort_by_key(|profile| profile.taken.map(|taken| taken.local_timestamp()));
+    dated.windows(2)
+        .filter(|pair| matches!((pair[0].time_zone(), pair[1].time_zone()), (Some(before), Some(after)) if before != after))
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+// The value that turns up most, and how many times. Ties go to the first.
+fn most_common(values: &[String]) -> Option<(String, usize)> {
+    let mut best: Option<(String, usize)> = None;
+    for value in values {
+        let count = values.iter().filter(|other| *other == value).count();
+        if best.as_ref().is_none_or(|(_, best)| count > *best) {
+            best = Some((value.clone(), count));
+        }
+    }
+    best
+}
+
+// Minutes east of UTC, as +HH:MM.
+fn format_offset(offset: i16) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+}
+
+// A photo, and when it was taken, for sessions.
+struct Shot {
+    path: PathBuf,
+    taken: ExifDateTime,
+    camera: Option<String>,
+}
+
+// How long a gap starts a new session, unless --gap says otherwise: 2h.
+const DEFAULT_GAP: i64 = 2 * 3600;
+
+// Group photos into sessions by the gaps between them.
+fn sessions(args: &[OsString]) -> bool {
+    let mut dir = None;
+    let mut gap = DEFAULT_GAP;
+    let mut include = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--gap") => gap = args.next().and_then(|gap| gap.to_str()).and_then(parse_duration).unwrap_or_else(|| usage()),
+            Some("--include") => {
+                let patterns = args.next().and_then(|patterns| patterns.to_str()).unwrap_or_else(|| usage());
+                include.extend(patterns.split(',').map(|pattern| pattern.trim().to_string()));
+            },
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => usage()
+        }
+    }
+    let dir = dir.unwrap_or_else(|| usage());
+    let mut files = Vec::new();
+    let mut ok = walk(&dir, &include, &mut files);
+
+    // When each photo was taken, or failing that, digitized, which is usually the same thing. The
+    // modified time is when some program last saved it, so that's no use here.
+    let mut shots = Vec::new();
+    let mut undated = Vec::new();
+    for path in files {
+        let exif = match Exif::from_path(&path) {
+            Ok(exif) => exif,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                ok = false;
+                continue
             }
-            // Read the whole thing. ? after function call here means unwrap result or returns err.
-            let buf = read_all(&file)?;
+        };
+        let taken = [DateTimeKind::Original, DateTimeKind::Digitized].iter()
+            .find_map(|&kind| exif.date_time(kind).ok().flatten());
+        match taken {
+            Some(taken) => {
+                let camera = camera_name(&exif);
+                shots.push(Shot { path, taken, camera });
+            },
+            None => undated.push(path)
+        }
+    }
+    shots.sort_by_key(|shot| shot.taken.local_timestamp());
 
-            // Defining some constants and finding offsets. Ref: https://www.media.mit.edu/pia/Research/deepview/exif.html
-            let tiff_header_marker = bytes_from_str("4949");
-            let tiff_header_offset = find(&buf, &tiff_header_marker);
+    for (n, session) in split_sessions(&shots, gap).iter().enumerate() {
+        let (first, last) = (&session[0], &session[session.len() - 1]);
+        let length = last.taken.local_timestamp() - first.taken.local_timestamp();
+        let photos = if session.len() == 1 { "photo" } else { "photos" };
+        println!("Session {}: {} {}, {} to {} ({})", n + 1, session.len(), photos, first.taken, last.taken, format_duration(length));
+        let mut cameras: Vec<&str> = session.iter().filter_map(|shot| shot.camera.as_deref()).collect();
+        cameras.sort_unstable();
+        cameras.dedup();
+        if !cameras.is_empty() {
+            println!("  cameras: {}", cameras.join(", "));
+        }
+        let longest_gap = session.windows(2).map(|pair| pair[1].taken.local_timestamp() - pair[0].taken.local_timestamp()).max();
+        if let Some(longest_gap) = longest_gap {
+            println!("  longest gap: {}", format_duration(longest_gap));
+        }
+        println!("  first: {}", first.path.display());
+        println!("  last: {}", last.path.display());
+    }
+    if !undated.is_empty() {
+        println!("No date taken, so in no session:");
+        for path in &undated {
+            println!("  {}", path.display());
+        }
+    }
+    ok
+}
 
-            let ifd_make_marker = bytes_from_str("0f01");
-            let ifd_make_offset = find(&buf, &ifd_make_marker);
+// A new session starts wherever the gap since the last photo (in order) is more than gap seconds.
+fn split_sessions(shots: &[Shot], gap: i64) -> Vec<&[Shot]> {
+    let mut sessions = Vec::new();
+    let mut start = 0;
+    for i in 1..=shots.len() {
+        if i == shots.len() || shots[i].taken.local_timestamp() - shots[i - 1].taken.local_timestamp() > gap {
+            sessions.push(&shots[start..i]);
+            start = i;
+        }
+    }
+    sessions
+}
 
-            // Create our IFD structure from our byte buffer and an ifd offset.
-            let ifd_make_tag = IFD::from_offset(&buf, ifd_make_offset);
+// Make and Model, or whichever of them there is. Model often repeats the make ("Canon EOS 40D"),
+// in which case the model alone will do.
+fn camera_name(exif: &Exif) -> Option<String> {
+    let ascii = |tag: TagId| match exif.value(exif.entry(tag)?).ok()? {
+        TagValue::Ascii(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        _ => None
+    };
+    match (ascii(TagId::Make), ascii(TagId::Model)) {
+        (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model)
+    }
+}
 
-            // If we couldn't find either of our offsets, we probably can't continue.
-            if tiff_header_offset == 0 || ifd_make_offset == 0 {
-                println!("Unable to find apropriate offsets. Exif data either not present or adheres to some other format.");
-            } else {
-                // Print out the first 100 bytes for reference -- our tags should be in that range.
-                println!("First 100 file bytes, wrapped to 10:");
-                for step in (0..100).step_by(10) {
-                    print!("{:2}: ", step);
-                    print_offset(&buf, step, 10);
+// A length of time like 90s, 30m, 2h or 1d, in seconds. A plain number is seconds.
+fn parse_duration(s: &str) -> Option<i64> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s")
+    };
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None
+    };
+    number.parse::<i64>().ok()?.checked_mul(scale)
+}
+
+// Seconds as the two biggest units that matter, like 1h 5m or 45s.
+fn format_duration(seconds: i64) -> String {
+    let (days, hours, minutes, seconds) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60, seconds % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, _) => format!("{}m {}s", minutes, seconds),
+        (0, _, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours)
+    }
+}
+
+// Write a copy of one file with metadata from others layered over it.
+fn merge(args: &[OsString]) -> bool {
+    let mut base = None;
+    let mut overlays = Vec::new();
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().map(PathBuf::from).unwrap_or_else(|| usage());
+        match arg.to_str() {
+            Some("--base") if base.is_none() => base = Some(value),
+            Some("--overlay") => overlays.push(value),
+            Some("--out") if out.is_none() => out = Some(value),
+            _ => usage()
+        }
+    }
+    let (base, out) = match (base, out) {
+        (Some(base), Some(out)) if !overlays.is_empty() => (base, out),
+        _ => usage()
+    };
+
+    // Sidecars are the obvious thing to merge in, but we don't read them yet, and they'd only fail
+    // confusingly below with "no exif".
+    for overlay in &overlays {
+        let extension = overlay.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        if let Some("xmp" | "json") = extension.as_deref() {
+            eprintln!("Can't merge {}: we can only read exif from images so far, not XMP or JSON.", overlay.display());
+            return false
+        }
+    }
+    let read = |path: &Path| Exif::from_path(path).map_err(|e| eprintln!("{}: {}", path.display(), e)).ok();
+    let mut exif = match read(&base) {
+        Some(exif) => exif,
+        None => return false
+    };
+    let mut layers = Vec::new();
+    for overlay in &overlays {
+        match read(overlay) {
+            Some(layer) => layers.push(layer),
+            None => return false
+        }
+    }
+    let layers: Vec<&Exif> = layers.iter().collect();
+    if let Err(e) = exif.merge(&layers).and_then(|()| exif.save(&out)) {
+        eprintln!("Couldn't merge onto {}: {}", base.display(), e);
+        return false
+    }
+    println!("Wrote {}.", out.display());
+    true
+}
+
+// Write a copy of a file without some of its tags.
+fn remove(patterns: &OsString, args: &[OsString]) -> bool {
+    let patterns: Vec<&str> = patterns.to_str().unwrap_or_else(|| usage()).split(',').map(str::trim).collect();
+    let (file, out) = file_and_out(args, "-o");
+    let mut exif = match Exif::from_path(&file) {
+        Ok(exif) => exif,
+        Err(e) => {
+            eprintln!("{}: {}", file.display(), e);
+            return false
+        }
+    };
+
+    let names = tags_matching(&exif, &patterns);
+    let edits: Vec<Edit> = names.iter().map(|&(kind, tag)| Edit::Remove(kind, tag)).collect();
+    if edits.is_empty() {
+        eprintln!("{} has no tags matching {}.", file.display(), patterns.join(","));
+        return false
+    }
+    if let Err(e) = exif.edit(&edits).and_then(|()| exif.save(&out)) {
+        eprintln!("Couldn't remove tags from {}: {}", file.display(), e);
+        return false
+    }
+    for (kind, tag) in names {
+        println!("Removed {} from {}", tag, kind);
+    }
+    println!("Wrote {}.", out.display());
+    true
+}
+
+// Every tag the file has that one of the patterns matches, once, however many entries it has.
+fn tags_matching(exif: &Exif, patterns: &[&str]) -> Vec<(IfdKind, TagId)> {
+    let mut names = Vec::new();
+    for directory in exif.directories() {
+        for entry in &directory.entries {
+            let tag = entry.tag_id(directory.kind);
+            let name = tag.to_string();
+            if patterns.iter().any(|pattern| glob_match(pattern, &name)) && !names.contains(&(directory.kind, tag)) {
+                names.push((directory.kind, tag));
+            }
+        }
+    }
+    names
+}
+
+// Whether text matches a shell style pattern, where * matches any run of characters (including
+// none) and ? any one. Letters match either case, since nobody remembers how GPS tags are
+// capitalised.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let text: Vec<char> = text.chars().map(|c| c.to_ascii_lowercase()).collect();
+    // The classic backtracking matcher: when we hit a mismatch, let the last * we saw swallow one
+    // more character and try again from there.
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// The FILE and the output for commands like carve, which take one of each.
+fn file_and_out(args: &[OsString], out_flag: &str) -> (PathBuf, PathBuf) {
+    let mut file = None;
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == out_flag {
+            out = args.next().map(PathBuf::from);
+        } else if file.is_none() {
+            file = Some(PathBuf::from(arg));
+        } else {
+            usage();
+        }
+    }
+    match (file, out) {
+        (Some(file), Some(out)) => (file, out),
+        _ => usage()
+    }
+}
+
+// Read a whole file, if it's a jpeg, complaining if not.
+fn read_jpeg(file: &Path) -> Option<Vec<u8>> {
+    let buf = match fs::read(file) {
+        Ok(buf) => buf,
+        Err(e) => {
+            eprintln!("An error occurred while trying to open {}: {}", file.display(), e);
+            return None
+        }
+    };
+    let file_type = FileType::from_magic(&buf);
+    if file_type != FileType::Jpeg {
+        eprintln!("{} is a {} file. We can only do this to jpegs.", file.display(), file_type);
+        return None
+    }
+    Some(buf)
+}
+
+// A tiny file for selftest to read, and what it should find: its type and byte order, and a Make
+// tag of "HelloExif". None for files that shouldn't be readable at all.
+struct Fixture {
+    name: &'static str,
+    bytes: &'static [u8],
+    expected: Option<(FileType, ByteOrder)>,
+}
+
+// One of each format, in both byte orders between them, and a broken one. They're built into the
+// binary, so selftest works wherever it's copied to. See fixtures/README.md.
+const FIXTURES: &[Fixture] = &[
+    Fixture { name: "le.tif", bytes: include_bytes!("../fixtures/le.tif"), expected: Some((FileType::Tiff, ByteOrder::LittleEndian)) },
+    Fixture { name: "be.tif", bytes: include_bytes!("../fixtures/be.tif"), expected: Some((FileType::Tiff, ByteOrder::BigEndian)) },
+    Fixture { name: "bigtiff.tif", bytes: include_bytes!("../fixtures/bigtiff.tif"), expected: Some((FileType::Tiff, ByteOrder::LittleEndian)) },
+    Fixture { name: "bigtiff-be.tif", bytes: include_bytes!("../fixtures/bigtiff-be.tif"), expected: Some((FileType::Tiff, ByteOrder::BigEndian)) },
+    Fixture { name: "exif.jpg", bytes: include_bytes!("../fixtures/exif.jpg"), expected: Some((FileType::Jpeg, ByteOrder::LittleEndian)) },
+    Fixture { name: "exif.png", bytes: include_bytes!("../fixtures/exif.png"), expected: Some((FileType::Png, ByteOrder::BigEndian)) },
+    Fixture { name: "exif.heic", bytes: include_bytes!("../fixtures/exif.heic"), expected: Some((FileType::Heif, ByteOrder::LittleEndian)) },
+    Fixture { name: "truncated.jpg", bytes: include_bytes!("../fixtures/truncated.jpg"), expected: None },
+];
+
+// helloexif selftest. Every fixture is well formed (apart from the broken one), so they're read
+// strictly, and anything less than exactly what we expect is a failure.
+fn selftest() -> bool {
+    let mut passed = 0;
+    for fixture in FIXTURES {
+        let result = Exif::from_bytes_with(fixture.bytes, ParseMode::Strict);
+        let failure = match (&fixture.expected, result) {
+            (None, Err(_)) => None,
+            (None, Ok(_)) => Some("read, but it's broken and shouldn't have".to_string()),
+            (Some(_), Err(e)) => Some(e.to_string()),
+            (Some((file_type, byte_order)), Ok(exif)) => {
+                if exif.file_type() != *file_type {
+                    Some(format!("read as {}, not {}", exif.file_type(), file_type))
+                } else if exif.byte_order() != *byte_order {
+                    Some(format!("read as {}, not {}", exif.byte_order(), byte_order))
+                } else if exif.entry(TagId::ExifIFDPointer).is_some() && exif.exif_ifd().is_none() {
+                    Some("has an Exif IFD pointer, but no Exif IFD".to_string())
+                } else {
+                    match exif.make() {
+                        Ok(Some(make)) if make == "HelloExif" => None,
+                        Ok(make) => Some(format!("make tag is {:?}, not \"HelloExif\"", make)),
+                        Err(e) => Some(format!("make tag couldn't be read: {}", e))
+                    }
+                }
+            }
+        };
+        match failure {
+            None => {
+                println!("pass  {}", fixture.name);
+                passed += 1;
+            },
+            Some(why) => println!("FAIL  {}: {}", fixture.name, why)
+        }
+    }
+    println!("{} of {} passed.", passed, FIXTURES.len());
+    passed == FIXTURES.len()
+}
+
+// Name a recovered jpeg after its DateTimeOriginal, like 2008-05-30_15-56-01, since its real name
+// went with the filesystem. If it doesn't have one, the best we can do is where we found it.
+fn carved_name(jpeg: &CarvedJpeg) -> String {
+    let taken = Exif::from_bytes(&jpeg.data).ok().and_then(|exif| {
+        let entry = exif.entry(TagId::DateTimeOriginal)?;
+        match exif.value(entry).ok()? {
+            TagValue::Ascii(s) => Some(s.into_owned()),
+            _ => None
+        }
+    });
+    match taken {
+        // Only keep characters that are safe in a filename everywhere.
+        Some(taken) => taken.trim().chars().filter_map(|c| match c {
+            ':' => Some('-'),
+            ' ' => Some('_'),
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None
+        }).collect(),
+        None => format!("offset_{:010x}", jpeg.offset)
+    }
+}
+
+// dir/name.jpg, or dir/name_1.jpg (and so on) if that's taken, so photos taken in the same second
+// don't overwrite each other.
+fn unused_path(dir: &Path, name: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.jpg", name));
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{}_{}.jpg", name, n));
+        n += 1;
+    }
+    path
+}
+
+// Print everything we know about one file. Returns false if we couldn't read its exif.
+//
+// There are sample files included in the repository, to try this out with. They come from:
+// https://github.com/ianare/exif-samples/tree/master/jpg
+fn print_file(filename: &Path, options: &Options) -> bool {
+    println!("Reading file: {}", filename.display());
+
+    // Check what we've actually been handed, so we can complain about misnamed files even if
+    // they turn out to be something we can't read.
+    let file_type = match FileType::from_path(filename) {
+        Ok(file_type) => file_type,
+        // Couldn't open our file for some reason, so move on to the next one
+        Err(e) => {
+            println!("An error occurred while trying to open file: {}", e);
+            return false
+        }
+    };
+    println!("Detected file type: {}", file_type);
+    if !file_type.matches_extension(filename) {
+        eprintln!("Warning: {} looks like a {} file, but its extension says otherwise.", filename.display(), file_type);
+    }
+
+    // The library does the actual work.
+    let started = Instant::now();
+    let mut exif = match read_exif(filename, options, started) {
+        Ok(exif) => exif,
+        Err(e) => {
+            eprintln!("{}", e);
+            write_bug_report(filename, options, &e);
+            return false
+        }
+    };
+    exif.set_duplicates(options.duplicates);
+    let printed = print_exif(filename, &exif, options);
+    print_timing(filename, options, started, &exif);
+    printed
+}
+
+// Print everything in a file's exif, and what else we know about the file from it. Returns false
+// if something we were asked to do with it (write its thumbnail out, say) didn't work.
+fn print_exif(filename: &Path, exif: &Exif, options: &Options) -> bool {
+    let buf = exif.bytes();
+    let hidden = options.hidden_ranges(exif);
+
+    // Print out the first 100 bytes for reference -- our tags should be in that range.
+    println!("First 100 file bytes, wrapped to 10:");
+    for step in (0..buf.len().min(100)).step_by(10) {
+        print!("{:2}: ", step);
+        print_offset(buf, step, 10, &hidden);
+    }
+    println!();
+    // Display tiff header offset (jpegs have tiff format headers for exif, who knew)
+    println!("tiff offset: {}", exif.tiff_header_offset());
+    println!("byte order: {}", exif.byte_order());
+    println!("confidence: {}", exif.confidence());
+    // Print out every entry of every IFD, in numerical values, and hex values
+    for ifd in exif.directories() {
+        println!("{} offset: {}, {} entries (name | tag, type, count, value offset | le byte values | value):", ifd.kind, ifd.offset, ifd.entries.len());
+        for entry in &ifd.entries {
+            let name = entry.tag_id(ifd.kind);
+            if options.redacts(ifd.kind, name) {
+                println!("  {} | redacted", name);
+                continue;
+            }
+            match exif.value(entry) {
+                Ok(value) => match describe(name, &value) {
+                    Some(description) => println!("  {} | {} | {:x} | {} ({})", name, entry, entry, value, description),
+                    None => println!("  {} | {} | {:x} | {}", name, entry, entry, value)
+                },
+                Err(e) => println!("  {} | {} | {:x} | {}", name, entry, entry, e)
+            }
+        }
+        for (tag, n) in ifd.duplicates() {
+            println!("  Warning: {} appears {} times, using {}", TagId::from_id(ifd.kind, tag), n, exif.duplicates());
+        }
+    }
+    // Print out make value, or values, if it's duplicated and we were asked for all of them.
+    let makes = exif.entries_for(TagId::Make);
+    if makes.is_empty() {
+        println!("make tag value: missing");
+    }
+    for entry in makes {
+        match exif.value_string(entry) {
+            Ok(make) => println!("make tag value: '{}'", make),
+            Err(e) => println!("make tag value: couldn't be read: {}", e)
+        }
+    }
+    if let Some(raw) = exif.raw_format() {
+        println!("raw format: {}", raw);
+    }
+    if let Some(cr2) = exif.cr2_header() {
+        println!("cr2 version {}.{}, raw IFD offset: {}", cr2.major_version, cr2.minor_version, cr2.raw_ifd_offset);
+    }
+    // In a tiff, every numbered directory is an image, and how it's stored is worth knowing. Raw
+    // files keep their raw data in SubIFDs too.
+    if exif.file_type().is_tiff_based() {
+        let subifds = exif.directories().filter(|ifd| matches!(ifd.kind, IfdKind::SubIfd(_)));
+        for ifd in exif.ifds().iter().chain(subifds) {
+            match exif.image_structure(ifd) {
+                Ok(structure) => print_structure(ifd.kind, &structure),
+                Err(e) => println!("{} structure: couldn't be read: {}", ifd.kind, e)
+            }
+        }
+    }
+    // And when, if the camera's clock was set.
+    for kind in [DateTimeKind::Original, DateTimeKind::Digitized, DateTimeKind::Modified] {
+        match exif.date_time(kind) {
+            Ok(Some(date_time)) => println!("{}: {}", kind, date_time),
+            Ok(None) => {},
+            Err(e) => println!("{}: couldn't be read: {}", kind, e)
+        }
+    }
+    // Photos say where they were taken, if the camera knew.
+    match exif.gps() {
+        Ok(Some(_)) if options.redact.contains(&Redact::Gps) => println!("gps: redacted"),
+        Ok(Some(gps)) => println!("gps: {}", gps),
+        Ok(None) => {},
+        Err(e) => println!("gps: couldn't be read: {}", e)
+    }
+    // Canons say more about themselves in their maker notes.
+    match exif.canon_maker_note() {
+        Ok(Some(canon)) => print_canon_maker_note(&canon, options),
+        Ok(None) => {},
+        Err(e) => println!("canon maker note: couldn't be read: {}", e)
+    }
+    if let Some(out) = &options.thumbnail {
+        match exif.thumbnail() {
+            Some(thumbnail) => match fs::write(out, thumbnail) {
+                Ok(()) => println!("thumbnail: {} bytes -> {}", thumbnail.len(), out.display()),
+                Err(e) => {
+                    eprintln!("Couldn't write {}: {}", out.display(), e);
+                    return false
                 }
-                println!("");
-                // Display tiff header offset (jpegs have tiff format headers for exif, who knew)
-                println!("tiff offset: {}", tiff_header_offset);
-                println!("ifd make offset: {}", ifd_make_offset);
-                // Print out IFD structure in numerical values, and hex values
-                println!("ifd make numerical values: {}", ifd_make_tag);
-                println!("ifd make le byte values: {:x}", ifd_make_tag);
-                // Print out make value
-                print!("make tag value: ");
-                ifd_make_tag.print_value(&buf, tiff_header_offset);
+            },
+            None => {
+                eprintln!("{} doesn't have a thumbnail.", filename.display());
+                return false
             }
+        }
+    }
+    // Aerial and satellite images say where they are.
+    match exif.geotiff() {
+        Ok(Some(geo)) => print_geotiff(&geo),
+        Ok(None) => {},
+        Err(e) => println!("geotiff: couldn't be read: {}", e)
+    }
+    // Intact exif doesn't mean an intact picture, so check the rest of the file is there too, if
+    // we were asked to. That means reading all of it, where everything else only needs the start.
+    // We only know how to do that for jpegs.
+    if !options.check_image_data || exif.file_type() != FileType::Jpeg {
+        return true
+    }
+    match exif.image_data() {
+        Ok(ImageData::Complete) => println!("image data: complete"),
+        Ok(ImageData::Truncated) => {
+            println!("image data: truncated, the file ends before the EOI marker");
+            eprintln!("Warning: {} has exif, but its image data is cut short.", filename.display());
+        },
+        Err(e) => println!("image data: couldn't be checked: {}", e)
+    }
+    true
+}
+
+fn print_canon_maker_note(canon: &CanonMakerNote, options: &Options) {
+    let serial_number = match &canon.serial_number {
+        Some(_) if options.redact.contains(&Redact::Serial) => Some("redacted"),
+        serial_number => serial_number.as_deref(),
+    };
+    let fields = [
+        ("lens", canon.lens_model.as_deref()),
+        ("firmware", canon.firmware.as_deref()),
+        ("shooting mode", canon.shooting_mode.as_deref()),
+        ("serial number", serial_number),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            println!("canon {}: {}", name, value);
+        }
+    }
+}
+
+// Read a file's exif, and with --timing, say how long it took if we couldn't. If we could,
+// print_timing says, once we're done with the file.
+fn read_exif(filename: &Path, options: &Options, started: Instant) -> Result<Exif, ExifError> {
+    let exif = Exif::from_path_with(filename, options.mode);
+    if options.timing && exif.is_err() {
+        eprintln!("{}: failed in {:.2}ms", filename.display(), started.elapsed().as_secs_f64() * 1000.0);
+    }
+    exif
+}
+
+// With --timing, how long a file took us since started, and how much of it we read all told: the
+// start of it, for its exif, and all of it again if something needed the rest.
+fn print_timing(filename: &Path, options: &Options, started: Instant, exif: &Exif) {
+    if !options.timing {
+        return;
+    }
+    let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+    match exif.read_stats() {
+        Some(stats) => eprintln!("{}: done in {:.2}ms, {} bytes read, {} hits, {} misses", filename.display(), elapsed, stats.bytes_read, stats.hits, stats.misses),
+        None => eprintln!("{}: done in {:.2}ms", filename.display(), elapsed),
+    }
+}
+
+// If we were asked for one, bundle up what we need to reproduce a failure. See BugReport. Files we
+// couldn't open at all have nothing to report on.
+fn write_bug_report(filename: &Path, options: &Options, e: &ExifError) {
+    let out = match &options.bug_report {
+        Some(out) => out,
+        None => return
+    };
+    let buf = match fs::read(filename) {
+        Ok(buf) => buf,
+        Err(e) => {
+            eprintln!("Couldn't read {} for the bug report: {}", filename.display(), e);
+            return
+        }
+    };
+    let report = BugReport::new(&buf, options.mode, e);
+    match fs::write(out, report.to_tar()) {
+        Ok(()) => eprintln!("Wrote a bug report to {}. It has {}'s metadata, but none of its image data.", out.display(), filename.display()),
+        Err(e) => eprintln!("Couldn't write {}: {}", out.display(), e)
+    }
+}
+
+// The image structure tags, tiffinfo style.
+fn print_structure(kind: IfdKind, structure: &ImageStructure) {
+    let dimension = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_else(|| "?".to_string());
+    println!("{} structure:", kind);
+    println!("  size: {} x {}", dimension(structure.width), dimension(structure.length));
+    println!("  samples per pixel: {}", structure.samples_per_pixel);
+    println!("  bits per sample: {:?}", structure.bits_per_sample);
+    println!("  sample format: {}", structure.sample_format_names().join(", "));
+    if let Some(photometric) = structure.photometric_interpretation_name() {
+        println!("  photometric interpretation: {}", photometric);
+    }
+    println!("  compression: {}", structure.compression_name());
+    println!("  predictor: {}", structure.predictor_name());
+    println!("  planar configuration: {}", structure.planar_configuration_name());
+    match structure.layout {
+        Layout::Strips { rows_per_strip, count } => {
+            println!("  stripped: {} strips of {} rows", count, dimension(rows_per_strip));
         },
-        // Couldn't open our file for some reason, so exit
-        Err(e) => println!("An error occurred while trying to open file: {}", e)
+        Layout::Tiles { width, length, count } => {
+            println!("  tiled: {} tiles of {} x {}", count, width, length);
+        },
+        Layout::None => println!("  no pixel data")
+    }
+}
+
+// The coordinate system and resolution, which is what GIS folks want to know first, then the
+// tiepoints and every key, for anyone who wants the details.
+fn print_geotiff(geo: &GeoTiff) {
+    println!("geotiff:");
+    match geo.crs() {
+        Some(crs) => println!("  crs: {}", crs),
+        None => println!("  crs: unknown")
+    }
+    if let Some(model_type) = geo.model_type() {
+        println!("  model type: {}", model_type);
+    }
+    if let Some((x, y, _)) = geo.pixel_scale {
+        println!("  resolution: {} x {} {} per pixel", x, y, geo.linear_units().unwrap_or("units"));
+    }
+    for tiepoint in &geo.tiepoints {
+        println!("  tiepoint: raster {:?} -> model {:?}", tiepoint.raster, tiepoint.model);
+    }
+    if let Some(transformation) = &geo.transformation {
+        println!("  transformation: {:?}", transformation);
+    }
+    for key in &geo.keys {
+        println!("  {}", key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn profile(name: &str, camera: &str, software: Option<&str>, taken: &str, offset: Option<i16>) -> Profile {
+        let taken = ExifDateTime::parse(taken).map(|taken| ExifDateTime { offset, ..taken });
+        Profile { path: PathBuf::from(name), camera: Some(camera.to_string()), software: software.map(str::to_string), maker_note: true, taken }
+    }
+
+    fn shot(taken: &str) -> Shot {
+        Shot { path: PathBuf::from(taken), taken: ExifDateTime::parse(taken).unwrap(), camera: None }
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("GPS*", "GPSLatitude"));
+        assert!(glob_match("GPS*", "GPS"));
+        assert!(!glob_match("GPS*", "ExifGPS"));
+        assert!(glob_match("*Date*", "DateTimeOriginal"));
+        assert!(glob_match("*Original", "DateTimeOriginal"));
+        assert!(glob_match("GPSLatitude?ef", "GPSLatitudeRef"));
+        assert!(!glob_match("GPSLatitude?", "GPSLatitude"));
+        assert!(glob_match("???e", "Make"));
+        assert!(glob_match("gps*", "GPSAltitude"));
+        assert!(glob_match("MAKE", "Make"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("", "Make"));
+        assert!(!glob_match("Artist", "Make"));
+        assert!(!glob_match("*a*a*a*b", "aaaaaaaaaaaaaaaaaaaa"));
+    }
+
+    #[test]
+    fn picks_the_tags_to_remove() {
+        let mut exif = Exif::from_bytes(FIXTURES[0].bytes).unwrap();
+        exif.set(TagId::Artist, "Jane Doe").unwrap();
+        exif.set(TagId::GPSLatitudeRef, "N").unwrap();
+        exif.set(TagId::GPSLongitudeRef, "W").unwrap();
+        // GPS* takes the pointer to the GPS IFD too, which takes the whole directory with it.
+        let gps = [(IfdKind::Ifd(0), TagId::GPSInfoIFDPointer), (IfdKind::Gps, TagId::GPSLatitudeRef), (IfdKind::Gps, TagId::GPSLongitudeRef)];
+        assert_eq!(tags_matching(&exif, &["GPS*"]), gps);
+        assert_eq!(tags_matching(&exif, &["gps*"]), gps);
+        assert_eq!(tags_matching(&exif, &["GPSL?titudeRef", "artist"]), [(IfdKind::Ifd(0), TagId::Artist), (IfdKind::Gps, TagId::GPSLatitudeRef)]);
+        assert_eq!(tags_matching(&exif, &["Copyright*"]), []);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("30m"), Some(1800));
+        assert_eq!(parse_duration("2h"), Some(7200));
+        assert_eq!(parse_duration("1d"), Some(86400));
+        assert_eq!(parse_duration("0h"), Some(0));
+    }
+
+    #[test]
+    fn bad_durations() {
+        for bad in ["", "h", "2x", "2H", "-1h", "1.5h", "2h30m", " 2h", "99999999999999999999s", "200000000000000d"] {
+            assert_eq!(parse_duration(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(90), "1m 30s");
+        assert_eq!(format_duration(3900), "1h 5m");
+        assert_eq!(format_duration(90000), "1d 1h");
+    }
+
+    #[test]
+    fn sessions_split_at_gaps_over_two_hours() {
+        assert_eq!(parse_duration("2h"), Some(DEFAULT_GAP));
+        let shots = [
+            shot("2020:01:01 10:00:00"),
+            shot("2020:01:01 12:00:00"),
+            shot("2020:01:01 14:00:01"),
+            shot("2020:01:02 09:00:00"),
+        ];
+        let lengths: Vec<usize> = split_sessions(&shots, DEFAULT_GAP).iter().map(|session| session.len()).collect();
+        assert_eq!(lengths, [2, 1, 1]);
+        let lengths: Vec<usize> = split_sessions(&shots, 86400).iter().map(|session| session.len()).collect();
+        assert_eq!(lengths, [4]);
+        assert!(split_sessions(&[], DEFAULT_GAP).is_empty());
+    }
+
+    #[test]
+    fn most_common_values() {
+        assert_eq!(most_common(&strings(&["a", "b", "b", "c"])), Some(("b".to_string(), 2)));
+        assert_eq!(most_common(&strings(&["a", "b", "a", "b"])), Some(("a".to_string(), 2)));
+        assert_eq!(most_common(&strings(&["a"])), Some(("a".to_string(), 1)));
+        assert_eq!(most_common(&[]), None);
+    }
+
+    #[test]
+    fn anomalies_stand_out_from_a_majority() {
+        let profiles = [
+            profile("1.jpg", "Canon EOS 40D", None, "2008:05:30 15:56:01", None),
+            profile("2.jpg", "Canon EOS 40D", None, "2008:05:30 15:57:01", None),
+            profile("3.jpg", "Canon EOS 40D", Some("GIMP 2.10"), "2008:05:30 15:58:01", None),
+        ];
+        let features: Vec<_> = profiles.iter().map(Profile::features).collect();
+        let normal = normal_features(&features);
+        assert_eq!(normal[0], Some(("Canon EOS 40D".to_string(), 3)));
+        assert_eq!(normal[1], Some(("none".to_string(), 2)));
+        assert!(differences(&features[0], &normal).is_empty());
+        assert_eq!(differences(&features[2], &normal), ["software is GIMP 2.10 (usually none)"]);
+
+        // Half isn't a majority, so nothing is normal, and nothing stands out.
+        let features = &features[1..];
+        let normal = normal_features(features);
+        assert_eq!(normal[1], None);
+        assert!(features.iter().all(|features| differences(features, &normal).is_empty()));
+    }
+
+    #[test]
+    fn time_zone_jumps_go_by_the_cameras_clock() {
+        // On the camera's clock, that's a, b, c, d, and the time zone never changes from one
+        // photo with an offset to the next. Going by UTC where there's an offset would put c and d
+        // (07:00Z and 08:00Z) before a (09:00Z), and see a jump from +09:00 to +01:00.
+        let profiles = [
+            profile("c.jpg", "X", None, "2020:01:01 16:00:00", Some(540)),
+            profile("a.jpg", "X", None, "2020:01:01 10:00:00", Some(60)),
+            profile("b.jpg", "X", None, "2020:01:01 12:00:00", None),
+            profile("d.jpg", "X", None, "2020:01:01 17:00:00", Some(540)),
+        ];
+        let jumps: Vec<_> = time_zone_jumps(&profiles).iter().map(|(before, after)| (before.path.clone(), after.path.clone())).collect();
+        assert!(jumps.is_empty());
+        let profiles = [
+            profile("b.jpg", "X", None, "2020:01:01 12:00:00", Some(60)),
+            profile("a.jpg", "X", None, "2020:01:01 10:00:00", Some(60)),
+            profile("c.jpg", "X", None, "2020:01:01 16:00:00", Some(540)),
+        ];
+        let jumps: Vec<_> = time_zone_jumps(&profiles).iter().map(|(before, after)| (before.path.clone(), after.path.clone())).collect();
+        assert_eq!(jumps, [(PathBuf::from("b.jpg"), PathBuf::from("c.jpg"))]);
     }
-    // This is required because our main function definition returns an io::Result type (so that we
-    // can use ? here and elsewhere as a shorthand, instead of full result blocks.
-    Ok(())
 }
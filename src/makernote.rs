@@ -0,0 +1,257 @@
+use crate::error::{ExifError, Result};
+use crate::ifd::IFD;
+use crate::tag::TagId;
+use crate::tiff::{slice_at, to_usize, TiffVariant};
+use crate::value::TagValue;
+use crate::Exif;
+
+// Maker notes are where camera makers put everything the exif spec doesn't have a tag for. The
+// spec just calls MakerNote UNDEFINED, and every maker does something different with it, so each
+// one needs its own decoder, and the Make tag is how we know which. Canon's is the easiest: an
+// IFD, like any other, starting at the very first byte of the maker note, in the file's byte
+// order, with offsets relative to the tiff header like the rest of the file's. Software that moves
+// the maker note without fixing its offsets breaks that, which is why the values are all optional.
+// Ref: https://exiftool.org/TagNames/Canon.html
+
+// The Canon maker note tags we read.
+const CAMERA_SETTINGS: u16 = 0x0001;
+const FIRMWARE_VERSION: u16 = 0x0007;
+const SERIAL_NUMBER: u16 = 0x000c;
+const LENS_MODEL: u16 = 0x0095;
+
+// Where things are in CameraSettings, a list of SHORTs. The first one is the list's length in
+// bytes, so they count from 1.
+const EASY_MODE: usize = 11;
+const EXPOSURE_MODE: usize = 20;
+
+// The more interesting parts of a Canon maker note.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CanonMakerNote {
+    // Like "EF-S18-55mm f/3.5-5.6 IS". Only cameras from around 2008 on write this.
+    pub lens_model: Option<String>,
+    // Like "Firmware Version 1.1.1".
+    pub firmware: Option<String>,
+    // The mode dial: an exposure mode like "Aperture-priority AE", or for the automatic and scene
+    // modes, which one, like "Portrait".
+    pub shooting_mode: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+impl CanonMakerNote {
+    // Read what there is of it. Ok(None) if the file isn't from a Canon, or doesn't have a maker
+    // note, and an error if its maker note isn't a directory we can read.
+    pub fn read(exif: &Exif) -> Result<Option<Self>> {
+        let is_canon = exif.make().ok().flatten().is_some_and(|make| make.trim().to_ascii_lowercase().starts_with("canon"));
+        let maker_note = match exif.entry(TagId::MakerNote) {
+            Some(maker_note) if is_canon => maker_note,
+            _ => return Ok(None)
+        };
+        let entries = read_entries(exif, maker_note)?;
+        let value = |tag: u16| entries.iter().find(|entry| entry.tag == tag).and_then(|entry| exif.value(entry).ok());
+        let ascii = |tag: u16| match value(tag) {
+            Some(TagValue::Ascii(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            _ => None
+        };
+
+        let shooting_mode = match value(CAMERA_SETTINGS) {
+            Some(TagValue::Short(settings)) => shooting_mode(&settings).map(str::to_string),
+            _ => None
+        };
+        let model = exif.entry(TagId::Model).and_then(|entry| exif.value_string(entry).ok());
+        // The EOS-1D models write the serial number as two halves: the first in hex, the second
+        // in decimal. The rest write it as the plain number.
+        let serial_number = match value(SERIAL_NUMBER).and_then(|value| value.to_u64s()).and_then(|v| v.first().copied()) {
+            Some(serial) if model.as_ref().is_some_and(|model| model.contains("EOS-1D")) => {
+                Some(format!("{:04x}{:05}", serial >> 16, serial & 0xffff))
+            },
+            Some(serial) => Some(serial.to_string()),
+            None => None
+        };
+
+        Ok(Some(CanonMakerNote {
+            lens_model: ascii(LENS_MODEL),
+            firmware: ascii(FIRMWARE_VERSION),
+            shooting_mode,
+            serial_number,
+        }))
+    }
+}
+
+// The entries of the directory the maker note is. Canon have only ever written plain tiff, so its
+// entries are 12 bytes, whatever the file around it is.
+fn read_entries(exif: &Exif, maker_note: &IFD) -> Result<Vec<IFD>> {
+    let (tiff, byte_order, variant) = (exif.tiff(), exif.byte_order(), TiffVariant::Classic);
+    let offset = maker_note.data_offset().ok_or(ExifError::BadMarker("Canon maker note, it's too short to be a directory"))?;
+    let offset = to_usize(offset, "Canon maker note")?;
+    let count = byte_order.u16_at(tiff, offset, "Canon maker note entry count")? as usize;
+    let start = offset + variant.count_size();
+    if slice_at(tiff, start, count * variant.entry_size()).is_none() {
+        return Err(ExifError::TruncatedBuffer("Canon maker note entries"));
+    }
+    (0..count).map(|i| IFD::from_offset(tiff, start + i * variant.entry_size(), byte_order, variant)).collect()
+}
+
+// What the mode dial was set to, from CameraSettings.
+fn shooting_mode(settings: &[u16]) -> Option<&'static str> {
+    let exposure_mode = match settings.get(EXPOSURE_MODE)? {
+        // The automatic and scene modes, where EasyMode says which.
+        0 => return easy_mode(*settings.get(EASY_MODE)?),
+        1 => "Program AE",
+        2 => "Shutter speed priority AE",
+        3 => "Aperture-priority AE",
+        4 => "Manual",
+        5 => "Depth-of-field AE",
+        6 => "M-Dep",
+        7 => "Bulb",
+        8 => "Flexible-priority AE",
+        _ => return None
+    };
+    Some(exposure_mode)
+}
+
+fn easy_mode(mode: u16) -> Option<&'static str> {
+    let name = match mode {
+        0 => "Full auto",
+        1 => "Manual",
+        2 => "Landscape",
+        3 => "Fast shutter",
+        4 => "Slow shutter",
+        5 => "Night",
+        6 => "Gray Scale",
+        7 => "Sepia",
+        8 => "Portrait",
+        9 => "Sports",
+        10 => "Macro",
+        11 => "Black & White",
+        12 => "Pan focus",
+        13 => "Vivid",
+        14 => "Neutral",
+        15 => "Flash Off",
+        16 => "Long Shutter",
+        17 => "Super Macro",
+        18 => "Foliage",
+        19 => "Indoor",
+        20 => "Fireworks",
+        21 => "Beach",
+        22 => "Underwater",
+        23 => "Snow",
+        24 => "Kids & Pets",
+        25 => "Night Snapshot",
+        26 => "Digital Macro",
+        27 => "My Colors",
+        28 => "Movie Snap",
+        _ => return None
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::ParseMode;
+
+    type Entry = (u16, u16, u32, Vec<u8>);
+
+    fn ascii(tag: u16, s: &str) -> Entry {
+        (tag, 2, s.len() as u32 + 1, format!("{}\0", s).into_bytes())
+    }
+
+    // A little endian directory at `at` in the tiff data, with its values after it.
+    fn directory(entries: &[Entry], at: usize) -> Vec<u8> {
+        let mut directory = (entries.len() as u16).to_le_bytes().to_vec();
+        let mut data = Vec::new();
+        let data_start = at + 2 + 12 * entries.len() + 4;
+        for (tag, tag_type, count, value) in entries {
+            directory.extend_from_slice(&tag.to_le_bytes());
+            directory.extend_from_slice(&tag_type.to_le_bytes());
+            directory.extend_from_slice(&count.to_le_bytes());
+            if value.len() <= 4 {
+                directory.extend_from_slice(&[value.as_slice(), &[0; 4][value.len()..]].concat());
+            } else {
+                directory.extend_from_slice(&((data_start + data.len()) as u32).to_le_bytes());
+                data.extend_from_slice(value);
+                if data.len() % 2 == 1 {
+                    data.push(0);
+                }
+            }
+        }
+        directory.extend_from_slice(&[0; 4]);
+        directory.extend_from_slice(&data);
+        directory
+    }
+
+    // A tiff from a Canon, whose maker note has the tags we read in it, in a directory of its own
+    // with offsets from the tiff header, the way Canon write them.
+    fn canon(make: &str, model: &str, serial: u32) -> Vec<u8> {
+        let mut settings = [0u16; 25];
+        settings[0] = 50;
+        settings[EASY_MODE] = 8;
+        let maker_note = |at: usize| directory(&[
+            (CAMERA_SETTINGS, 3, 25, settings.iter().flat_map(|v| v.to_le_bytes()).collect()),
+            ascii(FIRMWARE_VERSION, "Firmware Version 1.1.1"),
+            (SERIAL_NUMBER, 4, 1, serial.to_le_bytes().to_vec()),
+            ascii(LENS_MODEL, "EF-S18-55mm f/3.5-5.6 IS"),
+        ], at);
+        let ifd0 = |exif_at: u32| directory(&[
+            ascii(TagId::Make.id(), make),
+            ascii(TagId::Model.id(), model),
+            (TagId::ExifIFDPointer.id(), 4, 1, exif_at.to_le_bytes().to_vec()),
+        ], 8);
+        let exif_at = 8 + ifd0(0).len();
+        // The maker note is the Exif IFD's only value, so it's right after its one entry.
+        let maker_note = maker_note(exif_at + 2 + 12 + 4);
+        let exif = directory(&[(TagId::MakerNote.id(), 7, maker_note.len() as u32, maker_note)], exif_at);
+        [&b"II*\0\x08\0\0\0"[..], &ifd0(exif_at as u32), &exif].concat()
+    }
+
+    fn read(buf: &[u8]) -> Result<Option<CanonMakerNote>> {
+        CanonMakerNote::read(&Exif::from_bytes_with(buf, ParseMode::Strict)?)
+    }
+
+    #[test]
+    fn reads_a_canon_maker_note() {
+        let maker_note = read(&canon("Canon", "Canon EOS 40D", 1234567)).unwrap().unwrap();
+        assert_eq!(maker_note, CanonMakerNote {
+            lens_model: Some("EF-S18-55mm f/3.5-5.6 IS".to_string()),
+            firmware: Some("Firmware Version 1.1.1".to_string()),
+            shooting_mode: Some("Portrait".to_string()),
+            serial_number: Some("1234567".to_string()),
+        });
+    }
+
+    #[test]
+    fn eos_1d_serial_numbers_come_in_two_halves() {
+        let maker_note = read(&canon("Canon", "Canon EOS-1D Mark III", 0x12345678)).unwrap().unwrap();
+        assert_eq!(maker_note.serial_number.as_deref(), Some("123422136"));
+    }
+
+    #[test]
+    fn only_canons() {
+        assert_eq!(read(&canon("NIKON CORPORATION", "NIKON D3", 1234567)).unwrap(), None);
+        assert!(read(&canon("  canon ", "EOS 40D", 1)).unwrap().is_some());
+    }
+
+    #[test]
+    fn truncated_maker_note() {
+        let mut buf = canon("Canon", "Canon EOS 40D", 1234567);
+        let exif = Exif::from_bytes_with(&buf, ParseMode::Strict).unwrap();
+        let at = exif.entry(TagId::MakerNote).unwrap().data_offset().unwrap() as usize;
+        // More entries than there's room for.
+        buf[at..at + 2].copy_from_slice(&100u16.to_le_bytes());
+        assert!(matches!(read(&buf), Err(ExifError::TruncatedBuffer(_))));
+        // Cut off partway through the directory.
+        let exif = Exif::from_bytes_with(&buf[..at + 20], ParseMode::Lenient).unwrap();
+        assert!(matches!(CanonMakerNote::read(&exif), Err(ExifError::TruncatedBuffer(_))));
+    }
+
+    #[test]
+    fn shooting_modes() {
+        let mut settings = [0u16; 25];
+        assert_eq!(shooting_mode(&settings), Some("Full auto"));
+        settings[EXPOSURE_MODE] = 3;
+        assert_eq!(shooting_mode(&settings), Some("Aperture-priority AE"));
+        settings[EXPOSURE_MODE] = 99;
+        assert_eq!(shooting_mode(&settings), None);
+        assert_eq!(shooting_mode(&settings[..EXPOSURE_MODE]), None);
+    }
+}
@@ -0,0 +1,29 @@
+use std::fmt;
+
+// How fussy to be. Real files break the spec in all sorts of small ways (a missing next directory
+// offset, entries out of order, a thumbnail directory pointing off the end of the file), and most
+// of the time you'd rather get what can be read than nothing at all. But if you're checking files
+// before they go out the door, those are exactly the things you want to hear about.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ParseMode {
+    // Any spec violation is an error.
+    Strict,
+    // Read what we can, and step around what we can't. This is what we've always done.
+    #[default]
+    Lenient,
+}
+
+impl ParseMode {
+    pub fn is_strict(&self) -> bool {
+        *self == ParseMode::Strict
+    }
+}
+
+impl fmt::Display for ParseMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseMode::Strict => write!(f, "strict"),
+            ParseMode::Lenient => write!(f, "lenient"),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::Range;
+
+use crate::error::{ExifError, Result};
+use crate::mode::ParseMode;
+
+// Every PNG starts with these 8 bytes. Ref: https://www.w3.org/TR/png/#5PNG-file-signature
+pub const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// The chunk that holds exif, added in PNG 1.5 (2017), and the one that ends the file.
+const EXIF: &[u8] = b"eXIf";
+const IEND: &[u8] = b"IEND";
+
+// Find the exif in a PNG by walking its chunks. After the signature, a PNG is nothing but chunks,
+// each laid out as:
+//
+//   4 bytes  length of the data, big endian (PNG is big endian throughout)
+//   4 bytes  chunk type, four letters
+//   n bytes  the data
+//   4 bytes  CRC-32 of the type and data
+//
+// The eXIf chunk's data is a tiff header and everything after it, exactly like what follows
+// "Exif\0\0" in a jpeg, so we return where that data starts and ends. When we're being strict, we
+// check every chunk's CRC on the way past, since a chunk that doesn't match has been damaged, and
+// its length (which we're trusting to find the next one) might be too. Otherwise we trust the
+// lengths anyway, since a flipped bit in the pixels shouldn't cost us the exif.
+//
+// eXIf is supposed to come before the image data, but the spec asks readers to look for it after,
+// too, since some editors tack it on at the end. So we keep going until IEND.
+//
+// Before eXIf existed, some programs stored exif as hex in a "Raw profile type exif" text chunk,
+// usually zTXt, which is zlib compressed. We'd need an inflate implementation to read those, so
+// for now we don't.
+pub fn find_exif_chunk(buf: &[u8], mode: ParseMode) -> Result<Option<Range<usize>>> {
+    if !buf.starts_with(PNG_SIGNATURE) {
+        return Err(ExifError::BadMarker("PNG signature"));
+    }
+    let mut pos = PNG_SIGNATURE.len();
+    loop {
+        let header = buf.get(pos..pos + 8).ok_or(ExifError::TruncatedBuffer("PNG chunk header"))?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_type = &header[4..8];
+        let data_start = pos + 8;
+        let data_end = data_start.checked_add(length).ok_or(ExifError::InvalidOffset("PNG chunk"))?;
+        // The CRC covers the type as well as the data.
+        let covered = buf.get(pos + 4..data_end).ok_or(ExifError::TruncatedBuffer("PNG chunk"))?;
+        let crc = buf.get(data_end..data_end + 4).ok_or(ExifError::TruncatedBuffer("PNG chunk CRC"))?;
+        if mode.is_strict() && crc32(covered) != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+            return Err(ExifError::BadMarker("PNG chunk CRC"));
+        }
+        if chunk_type == EXIF {
+            return Ok(Some(data_start..data_end));
+        }
+        if chunk_type == IEND {
+            return Ok(None);
+        }
+        pos = data_end + 4;
+    }
+}
+
+// The CRC-32 PNG uses (the same one as zip and ethernet), a bit at a time. There are faster ways,
+// with a lookup table, but we're only reading files of a megabyte or so, and this is plenty quick
+// for that.
+// Ref: https://www.w3.org/TR/png/#D-CRCAppendix
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A big endian tiff with a single entry, a Make of "HelloExif".
+    const TIFF: &[u8] = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x0f\0\x02\0\0\0\x0a\0\0\0\x1a\0\0\0\0HelloExif\0";
+
+    fn chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let covered = [chunk_type, data].concat();
+        [&(data.len() as u32).to_be_bytes()[..], &covered, &crc32(&covered).to_be_bytes()].concat()
+    }
+
+    // A PNG with the given chunks between IHDR and IEND.
+    fn png(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let ihdr = chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]);
+        [PNG_SIGNATURE.to_vec(), ihdr, chunks.concat(), chunk(IEND, &[])].concat()
+    }
+
+    fn find(buf: &[u8], mode: ParseMode) -> Result<Option<&[u8]>> {
+        Ok(find_exif_chunk(buf, mode)?.map(|range| &buf[range]))
+    }
+
+    #[test]
+    fn crc32_of_iend() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn finds_exif_before_and_after_the_image_data() {
+        let idat = chunk(b"IDAT", &[0x78, 0x9c, 0x63, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01]);
+        let before = png(&[chunk(EXIF, TIFF), idat.clone()]);
+        let after = png(&[idat.clone(), chunk(EXIF, TIFF)]);
+        let without = png(&[idat]);
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            assert_eq!(find(&before, mode).unwrap(), Some(TIFF));
+            assert_eq!(find(&after, mode).unwrap(), Some(TIFF));
+            assert_eq!(find(&without, mode).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn corrupted_crcs_are_only_errors_when_strict() {
+        let idat = chunk(b"IDAT", &[0x78, 0x9c, 0x63, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01]);
+        let mut buf = png(&[idat, chunk(EXIF, TIFF)]);
+        // A bit flipped in the image data.
+        let at = buf.windows(4).position(|bytes| bytes == b"IDAT").unwrap() + 6;
+        buf[at] ^= 1;
+        assert!(matches!(find(&buf, ParseMode::Strict), Err(ExifError::BadMarker(_))));
+        assert_eq!(find(&buf, ParseMode::Lenient).unwrap(), Some(TIFF));
+
+        // And in the exif itself, which we'd rather have than not.
+        let mut buf = png(&[chunk(EXIF, TIFF)]);
+        let at = buf.windows(4).position(|bytes| bytes == EXIF).unwrap() + 4 + TIFF.len();
+        buf[at] ^= 0xff;
+        assert!(matches!(find(&buf, ParseMode::Strict), Err(ExifError::BadMarker(_))));
+        assert_eq!(find(&buf, ParseMode::Lenient).unwrap(), Some(TIFF));
+    }
+
+    #[test]
+    fn broken_pngs() {
+        let buf = png(&[chunk(EXIF, TIFF)]);
+        assert!(matches!(find(&buf[1..], ParseMode::Lenient), Err(ExifError::BadMarker(_))));
+        for length in [PNG_SIGNATURE.len() + 4, PNG_SIGNATURE.len() + 20, buf.len() - 13] {
+            assert!(matches!(find(&buf[..length], ParseMode::Lenient), Err(ExifError::TruncatedBuffer(_))), "{}", length);
+        }
+        // A length that runs off the end of the file.
+        let mut buf = buf;
+        let at = PNG_SIGNATURE.len();
+        buf[at..at + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(find(&buf, ParseMode::Lenient).is_err());
+    }
+}
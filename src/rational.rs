@@ -0,0 +1,169 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+// Exif stores anything that isn't a whole number as a fraction: two LONGs, numerator then
+// denominator, for RATIONAL, and two SLONGs for SRATIONAL. Fractions are what photographers think
+// in anyway (an exposure of 1/250 s is a lot more familiar than 0.004 s), so we keep them as
+// fractions, and only turn them into floats when asked. Ref: https://www.exif.org/Exif2-2.PDF, 4.6.2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: u32,
+    denominator: u32,
+}
+
+// The signed kind, for things like exposure bias and shutter speed APEX values, which can go
+// negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SRational {
+    numerator: i32,
+    denominator: i32,
+}
+
+impl Rational {
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Rational { numerator, denominator }
+    }
+
+    pub fn numerator(&self) -> u32 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> u32 {
+        self.denominator
+    }
+
+    // The fraction as a float. None when the denominator is 0, which some cameras write to mean
+    // "don't know", and which isn't a number either way.
+    pub fn to_f64(&self) -> Option<f64> {
+        if self.denominator == 0 {
+            None
+        } else {
+            Some(self.numerator as f64 / self.denominator as f64)
+        }
+    }
+
+    // In lowest terms, so 10/2500 becomes 1/250. A zero denominator is left alone, since there's
+    // nothing sensible to reduce it to.
+    pub fn simplify(&self) -> Self {
+        if self.denominator == 0 {
+            return *self;
+        }
+        let divisor = gcd(self.numerator as u64, self.denominator as u64) as u32;
+        Rational::new(self.numerator / divisor, self.denominator / divisor)
+    }
+}
+
+impl SRational {
+    pub fn new(numerator: i32, denominator: i32) -> Self {
+        SRational { numerator, denominator }
+    }
+
+    pub fn numerator(&self) -> i32 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> i32 {
+        self.denominator
+    }
+
+    // See Rational::to_f64.
+    pub fn to_f64(&self) -> Option<f64> {
+        if self.denominator == 0 {
+            None
+        } else {
+            Some(self.numerator as f64 / self.denominator as f64)
+        }
+    }
+
+    // In lowest terms, with the sign on the numerator, so 2/-6 becomes -1/3. We work in i64 so
+    // that negating i32::MIN can't overflow, and leave the fraction alone in the one case where the
+    // result still wouldn't fit. A zero denominator is left alone, like Rational's.
+    pub fn simplify(&self) -> Self {
+        if self.denominator == 0 {
+            return *self;
+        }
+        let (mut numerator, mut denominator) = (self.numerator as i64, self.denominator as i64);
+        let divisor = gcd(numerator.unsigned_abs(), denominator.unsigned_abs()) as i64;
+        numerator /= divisor;
+        denominator /= divisor;
+        if denominator < 0 {
+            numerator = -numerator;
+            denominator = -denominator;
+        }
+        match (i32::try_from(numerator), i32::try_from(denominator)) {
+            (Ok(numerator), Ok(denominator)) => SRational::new(numerator, denominator),
+            _ => *self
+        }
+    }
+}
+
+// Euclid's. Never 0 here, since the denominator never is.
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+// Fractions display as they're stored, unsimplified, since that's what's in the file.
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+impl fmt::Display for SRational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simplifies() {
+        assert_eq!(Rational::new(10, 2500).simplify(), Rational::new(1, 250));
+        assert_eq!(Rational::new(0, 5).simplify(), Rational::new(0, 1));
+        assert_eq!(Rational::new(7, 0).simplify(), Rational::new(7, 0));
+        assert_eq!(Rational::new(u32::MAX, u32::MAX).simplify(), Rational::new(1, 1));
+    }
+
+    #[test]
+    fn simplifies_with_the_sign_on_the_numerator() {
+        assert_eq!(SRational::new(2, -6).simplify(), SRational::new(-1, 3));
+        assert_eq!(SRational::new(-2, -6).simplify(), SRational::new(1, 3));
+        assert_eq!(SRational::new(-3, 0).simplify(), SRational::new(-3, 0));
+        assert_eq!(SRational::new(i32::MIN, 2).simplify(), SRational::new(-1073741824, 1));
+        assert_eq!(SRational::new(i32::MIN, i32::MIN).simplify(), SRational::new(1, 1));
+        assert_eq!(SRational::new(1, i32::MIN).simplify(), SRational::new(1, i32::MIN));
+        // 2147483648/1 doesn't fit, so it stays as it was.
+        assert_eq!(SRational::new(i32::MIN, -1).simplify(), SRational::new(i32::MIN, -1));
+    }
+
+    #[test]
+    fn to_f64() {
+        assert_eq!(Rational::new(1, 4).to_f64(), Some(0.25));
+        assert_eq!(Rational::new(1, 0).to_f64(), None);
+        assert_eq!(SRational::new(-1, 2).to_f64(), Some(-0.5));
+        assert_eq!(SRational::new(i32::MIN, -1).to_f64(), Some(2147483648.0));
+        assert_eq!(SRational::new(0, 0).to_f64(), None);
+    }
+
+    #[test]
+    fn gcds() {
+        assert_eq!(gcd(10, 2500), 10);
+        assert_eq!(gcd(0, 5), 5);
+        assert_eq!(gcd(7, 13), 1);
+        assert_eq!(gcd(1 << 31, 1 << 31), 1 << 31);
+    }
+
+    #[test]
+    fn displays_as_stored() {
+        assert_eq!(Rational::new(10, 2500).to_string(), "10/2500");
+        assert_eq!(SRational::new(2, -6).to_string(), "2/-6");
+    }
+}
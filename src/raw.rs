@@ -0,0 +1,76 @@
+use std::fmt;
+
+use crate::filetype::FileType;
+use crate::tiff::ByteOrder;
+use crate::Exif;
+
+// Raw camera files: what the sensor saw, before the camera turned it into a jpeg. Most raw formats
+// are tiffs underneath, with the exif where you'd expect it, and the raw data in an extra
+// directory or two, so reading their basic exif is no different to reading a tiff. Each maker has
+// their own conventions on top, though, and it's handy to know which we're looking at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawFormat {
+    // Canon. Has its own magic, see Cr2Header. IFD0 is a full size jpeg, IFD1 a thumbnail, IFD2 an
+    // uncompressed preview, and IFD3 the raw data.
+    Cr2,
+    // Nikon. A plain big endian tiff, with the raw data in one of IFD0's SubIFDs.
+    Nef,
+    // Sony. A plain little endian tiff, with the raw data in one of IFD0's SubIFDs (or, in older
+    // ones, IFD0 itself).
+    Arw,
+}
+
+impl RawFormat {
+    // CR2s say so in their header. NEFs and ARWs don't have any magic of their own, so we go by
+    // who made the camera, which is as good as it gets short of the extension.
+    pub fn detect(exif: &Exif) -> Option<Self> {
+        match exif.file_type() {
+            FileType::Cr2 => Some(RawFormat::Cr2),
+            FileType::Tiff => {
+                let make = exif.make().ok()??.to_uppercase();
+                if make.starts_with("NIKON") {
+                    Some(RawFormat::Nef)
+                } else if make.starts_with("SONY") {
+                    Some(RawFormat::Arw)
+                } else {
+                    None
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for RawFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RawFormat::Cr2 => write!(f, "Canon CR2"),
+            RawFormat::Nef => write!(f, "Nikon NEF"),
+            RawFormat::Arw => write!(f, "Sony ARW"),
+        }
+    }
+}
+
+// The 8 bytes a CR2 has after its tiff header, where a plain tiff would go straight into IFD0:
+// "CR", a major and minor version (2 and 0), and the offset of the raw IFD (IFD3), so you can
+// get straight to it without walking the chain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cr2Header {
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub raw_ifd_offset: u32,
+}
+
+impl Cr2Header {
+    // `tiff` starts at the tiff header, which in a CR2 is the start of the file.
+    pub fn parse(tiff: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        if tiff.get(8..10) != Some(&b"CR"[..]) {
+            return None;
+        }
+        Some(Cr2Header {
+            major_version: *tiff.get(10)?,
+            minor_version: *tiff.get(11)?,
+            raw_ifd_offset: byte_order.u32_at(tiff, 12, "CR2 raw IFD offset").ok()?,
+        })
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::filetype::FileType;
+use crate::ifd::{IfdKind, IFD};
+use crate::jpeg::{APP1, EOI, EXIF_SIGNATURE, RST0, RST7, SOI, SOS, TEM};
+use crate::tag::TagId;
+use crate::tiff::{ByteOrder, TiffHeader, TiffVariant};
+use crate::value::type_size;
+
+// Most of a photo is the picture. The metadata is a few kilobytes at the front, so reading a whole
+// 50MB raw file to get at it is a waste, and reading it into memory is worse. This works out how
+// much of the start of a file the metadata needs, by seeking around its structure and only reading
+// the little bits that say where things are, and then reads just that much.
+//
+// We only know how to do that for jpegs and tiffs (raw formats included). Anything else, or a file
+// whose structure doesn't make sense, is read whole, since the parser might need any of it to
+// work around whatever's wrong. Plenty of tiff writers put the directories after the image data,
+// and since we read from the start, those end up read whole too.
+//
+// The reads that find the structure are a few bytes each, often right next to each other (a
+// directory's entry count, then its entries), and the prefix we end up reading covers all of them
+// again. So everything goes through PageCache, which reads the file a page at a time and keeps
+// what it's read, and no part of the file is read twice. That matters more the slower a seek is.
+
+// Directories we'll follow before deciding a file is going round in circles (or is just
+// nonsense), and reading it whole instead.
+const MAX_DIRECTORIES: usize = 256;
+
+// How much PageCache reads at a time. Directories and the values near them usually share a page.
+const PAGE_SIZE: u64 = 4096;
+
+// How reading a file's metadata went: how many reads came out of pages we already had, how many
+// had to go to the file, and how many bytes those read.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReadStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub bytes_read: u64,
+}
+
+// The start of the file, as much as its metadata needs, the length of the whole file, and how
+// reading it went.
+pub fn read_metadata<R: Read + Seek>(reader: &mut R) -> io::Result<(Vec<u8>, u64, ReadStats)> {
+    let length = reader.seek(SeekFrom::End(0))?;
+    let mut cache = PageCache { reader, length, pages: HashMap::new(), stats: ReadStats::default() };
+    let magic = cache.read_at(0, 16)?;
+    let end = match FileType::from_magic(&magic) {
+        FileType::Jpeg => jpeg_end(&mut cache)?,
+        FileType::Tiff | FileType::Cr2 => tiff_end(&mut cache, &magic)?,
+        _ => None
+    };
+    let end = end.map_or(length, |end| end.min(length));
+    let (buf, stats) = cache.into_prefix(end)?;
+    Ok((buf, length, stats))
+}
+
+// A file, read a page at a time, keeping every page read.
+struct PageCache<'r, R> {
+    reader: &'r mut R,
+    length: u64,
+    pages: HashMap<u64, Vec<u8>>,
+    stats: ReadStats,
+}
+
+impl<R: Read + Seek> PageCache<'_, R> {
+    // Up to length bytes from offset, or fewer if the file ends first.
+    fn read_at(&mut self, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        let end = offset.saturating_add(length as u64).min(self.length);
+        let mut buf = Vec::with_capacity(end.saturating_sub(offset) as usize);
+        let mut pos = offset;
+        while pos < end {
+            let start = pos / PAGE_SIZE * PAGE_SIZE;
+            let page = self.page(start)?;
+            let (from, to) = ((pos - start) as usize, ((end - start) as usize).min(page.len()));
+            // The file got shorter since we asked how long it was.
+            if from >= to {
+                break;
+            }
+            buf.extend_from_slice(&page[from..to]);
+            pos = start + to as u64;
+        }
+        Ok(buf)
+    }
+
+    // The page starting at start, reading it if we haven't already.
+    fn page(&mut self, start: u64) -> io::Result<&[u8]> {
+        if self.pages.contains_key(&start) {
+            self.stats.hits += 1;
+        } else {
+            let page = read_at(self.reader, start, PAGE_SIZE)?;
+            self.stats.misses += 1;
+            self.stats.bytes_read += page.len() as u64;
+            self.pages.insert(start, page);
+        }
+        Ok(&self.pages[&start])
+    }
+
+    // The first end bytes of the file, which is the last thing we read. The pages we have are
+    // used up as they go into it, so we never hold two copies of them, and the gaps between them
+    // are read in one go each, rather than a page at a time, since when a file is read whole that
+    // can be a lot of pages. This isn't a read anyone asked for, just putting the ones they did
+    // together, so the pages don't count as hits.
+    fn into_prefix(mut self, end: u64) -> io::Result<(Vec<u8>, ReadStats)> {
+        let mut buf = Vec::with_capacity(end as usize);
+        while (buf.len() as u64) < end {
+            let pos = buf.len() as u64;
+            match self.pages.remove(&pos) {
+                Some(page) => {
+                    buf.extend_from_slice(&page[..page.len().min((end - pos) as usize)]);
+                    if (page.len() as u64) < PAGE_SIZE {
+                        break;
+                    }
+                },
+                None => {
+                    let next = self.pages.keys().filter(|&&start| start > pos).min().map_or(end, |&start| start.min(end));
+                    let gap = read_at(self.reader, pos, next - pos)?;
+                    self.stats.misses += 1;
+                    self.stats.bytes_read += gap.len() as u64;
+                    let short = (gap.len() as u64) < next - pos;
+                    buf.extend_from_slice(&gap);
+                    if short {
+                        break;
+                    }
+                },
+            }
+        }
+        Ok((buf, self.stats))
+    }
+}
+
+// Up to length bytes from offset, straight from the reader.
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    reader.take(length).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+// Where the jpeg's exif segment ends. This hops from segment to segment the way
+// find_exif_segment does, but reads only each segment's marker and length (and an APP1's
+// signature) on the way. None if there's no exif segment, or the segments stop making sense
+// before we find it.
+fn jpeg_end<R: Read + Seek>(file: &mut PageCache<R>) -> io::Result<Option<u64>> {
+    let mut pos = 2;
+    while pos < file.length {
+        let header = file.read_at(pos, 4 + EXIF_SIGNATURE.len())?;
+        if header.len() < 4 || header[0] != 0xff {
+            return Ok(None);
+        }
+        match header[1] {
+            // Fill byte, the marker proper is next.
+            0xff => pos += 1,
+            TEM | RST0..=RST7 => pos += 2,
+            SOI | SOS | EOI => return Ok(None),
+            marker => {
+                let segment_length = u16::from_be_bytes([header[2], header[3]]) as u64;
+                if segment_length < 2 {
+                    return Ok(None);
+                }
+                let end = pos + 2 + segment_length;
+                if marker == APP1 && header[4..].starts_with(EXIF_SIGNATURE) {
+                    return Ok(Some(end));
+                }
+                pos = end;
+            }
+        }
+    }
+    Ok(None)
+}
+
+// Where the tiff's metadata ends: the end of the last directory or value that we can reach from
+// IFD0 through the chain and the pointer tags, or of the thumbnail. That's the same directories
+// the parser reads, and everything in them, but none of the image data their offsets point at.
+// (Raw files can have a full size preview jpeg pointed at like the thumbnail, in a SubIFD, which
+// we leave out too.) None if there are more directories than any real file has.
+fn tiff_end<R: Read + Seek>(file: &mut PageCache<R>, magic: &[u8]) -> io::Result<Option<u64>> {
+    let length = file.length;
+    let header = match TiffHeader::parse(magic) {
+        Ok(header) => header,
+        Err(_) => return Ok(None)
+    };
+    let (byte_order, variant) = (header.byte_order, header.variant);
+    let (count_size, entry_size, offset_size) = (variant.count_size() as u64, variant.entry_size() as u64, variant.offset_size() as u64);
+    let mut end = match variant {
+        TiffVariant::Classic => 8,
+        TiffVariant::Big => 16,
+    };
+    // Directories to read, and where they are in the IFD0 chain, if they're in it, so we know to
+    // follow on to the next one, and which one has the thumbnail.
+    let mut queue = vec![(header.ifd0_offset, Some(0))];
+    let mut seen = Vec::new();
+    while let Some((offset, chain)) = queue.pop() {
+        if offset == 0 || offset >= length || seen.contains(&offset) {
+            continue;
+        }
+        if seen.len() == MAX_DIRECTORIES {
+            return Ok(None);
+        }
+        seen.push(offset);
+
+        let count = file.read_at(offset, count_size as usize)?;
+        if count.len() < count_size as usize {
+            continue;
+        }
+        let count = match variant {
+            TiffVariant::Classic => byte_order.read_u16(&count) as u64,
+            TiffVariant::Big => byte_order.read_u64(&count),
+        };
+        // Like the parser, we cope with the next directory offset being cut off.
+        let entries_length = count.saturating_mul(entry_size);
+        let entries_end = (offset + count_size).saturating_add(entries_length);
+        if entries_end > length {
+            continue;
+        }
+        end = end.max(entries_end.saturating_add(offset_size).min(length));
+        let bytes = file.read_at(offset + count_size, (entries_length + offset_size) as usize)?;
+        let (entries, next) = bytes.split_at(entries_length as usize);
+
+        let mut thumbnail = (None, None);
+        for entry in entries.chunks(entry_size as usize) {
+            let entry = match IFD::parse(entry, byte_order, variant) {
+                Ok(entry) => entry,
+                Err(_) => continue
+            };
+            if let (Some(data_offset), Some(data_length)) = (entry.data_offset(), entry.data_length()) {
+                end = end.max(data_offset.saturating_add(data_length));
+            }
+            match TagId::from_id(IfdKind::Ifd(0), entry.tag) {
+                TagId::ExifIFDPointer | TagId::GPSInfoIFDPointer | TagId::InteroperabilityIFDPointer => {
+                    queue.push((entry.pointer(byte_order), None));
+                },
+                TagId::SubIFDs => {
+                    for sub_ifd in offsets(file, &entry, byte_order)? {
+                        queue.push((sub_ifd, None));
+                    }
+                },
+                TagId::JPEGInterchangeFormat => thumbnail.0 = Some(entry.pointer(byte_order)),
+                TagId::JPEGInterchangeFormatLength => thumbnail.1 = Some(entry.pointer(byte_order)),
+                _ => {}
+            }
+        }
+        if let (Some(1), (Some(thumbnail_offset), Some(thumbnail_length))) = (chain, thumbnail) {
+            end = end.max(thumbnail_offset.saturating_add(thumbnail_length));
+        }
+        if let Some(n) = chain {
+            let next = match (variant, next.len() as u64 == offset_size) {
+                (_, false) => 0,
+                (TiffVariant::Classic, true) => byte_order.read_u32(next) as u64,
+                (TiffVariant::Big, true) => byte_order.read_u64(next),
+            };
+            queue.push((next, Some(n + 1)));
+        }
+    }
+    Ok(Some(end))
+}
+
+// The offsets in a list of them, like SubIFDs, wherever they're stored. There are never more than
+// a handful, so we don't read more than MAX_DIRECTORIES of them.
+fn offsets<R: Read + Seek>(file: &mut PageCache<R>, entry: &IFD, byte_order: ByteOrder) -> io::Result<Vec<u64>> {
+    let size = match type_size(entry.tag_type) {
+        Some(size @ (4 | 8)) => size,
+        _ => return Ok(Vec::new())
+    };
+    let count = (entry.count as usize).min(MAX_DIRECTORIES);
+    let bytes = match entry.data_offset() {
+        Some(offset) => file.read_at(offset, count * size)?,
+        None => entry.inline_bytes(byte_order),
+    };
+    Ok(bytes.chunks_exact(size).take(count).map(|offset| match size {
+        4 => byte_order.read_u32(offset) as u64,
+        _ => byte_order.read_u64(offset),
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn file(length: usize) -> Cursor<Vec<u8>> {
+        Cursor::new((0..length).map(|i| (i % 251) as u8).collect())
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let mut reader = file(10000);
+        let data = reader.get_ref().clone();
+        let mut cache = PageCache { reader: &mut reader, length: 10000, pages: HashMap::new(), stats: ReadStats::default() };
+        assert_eq!(cache.read_at(0, 16).unwrap(), &data[..16]);
+        assert_eq!(cache.read_at(100, 20).unwrap(), &data[100..120]);
+        // Across the end of the first page, into the second.
+        assert_eq!(cache.read_at(4090, 10).unwrap(), &data[4090..4100]);
+        assert_eq!(cache.stats, ReadStats { hits: 2, misses: 2, bytes_read: 8192 });
+        // The two pages we have aren't read again, and the rest is read in one go.
+        let (buf, stats) = cache.into_prefix(10000).unwrap();
+        assert_eq!(buf, data);
+        assert_eq!(stats, ReadStats { hits: 2, misses: 3, bytes_read: 10000 });
+    }
+
+    #[test]
+    fn reads_short_final_pages() {
+        let mut reader = file(5000);
+        let data = reader.get_ref().clone();
+        let mut cache = PageCache { reader: &mut reader, length: 5000, pages: HashMap::new(), stats: ReadStats::default() };
+        assert_eq!(cache.read_at(4990, 100).unwrap(), &data[4990..]);
+        assert_eq!(cache.read_at(5000, 100).unwrap(), b"");
+        assert_eq!(cache.stats, ReadStats { hits: 0, misses: 1, bytes_read: 904 });
+        let (buf, stats) = cache.into_prefix(4995).unwrap();
+        assert_eq!(buf, &data[..4995]);
+        assert_eq!(stats, ReadStats { hits: 0, misses: 2, bytes_read: 5000 });
+    }
+
+    // The file is shorter than it was when we asked how long it was.
+    #[test]
+    fn copes_with_a_file_that_shrinks() {
+        let mut reader = file(5000);
+        let data = reader.get_ref().clone();
+        let mut cache = PageCache { reader: &mut reader, length: 9000, pages: HashMap::new(), stats: ReadStats::default() };
+        assert_eq!(cache.read_at(4096, 8000).unwrap(), &data[4096..]);
+        assert_eq!(cache.read_at(6000, 10).unwrap(), b"");
+        assert_eq!(cache.read_at(8190, 10).unwrap(), b"");
+        let (buf, _) = cache.into_prefix(9000).unwrap();
+        assert_eq!(buf, data);
+
+        let mut reader = file(5000);
+        let cache = PageCache { reader: &mut reader, length: 9000, pages: HashMap::new(), stats: ReadStats::default() };
+        assert_eq!(cache.into_prefix(9000).unwrap().0, data);
+    }
+
+    #[test]
+    fn reads_only_a_jpegs_metadata() {
+        let mut jpeg = include_bytes!("../fixtures/exif.jpg").to_vec();
+        let metadata = jpeg.len();
+        jpeg.extend_from_slice(&[0; 100000]);
+        let (buf, length, stats) = read_metadata(&mut Cursor::new(&jpeg)).unwrap();
+        assert_eq!((buf.len(), length), (metadata - 2, jpeg.len() as u64));
+        assert_eq!(stats.misses, 1);
+    }
+}
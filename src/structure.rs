@@ -0,0 +1,99 @@
+use crate::describe::{code_name, COMPRESSIONS, PHOTOMETRIC_INTERPRETATIONS, PLANAR_CONFIGURATIONS, PREDICTORS, SAMPLE_FORMATS};
+use crate::error::Result;
+use crate::ifd::Directory;
+use crate::tag::TagId;
+use crate::Exif;
+
+// How an image in a TIFF is laid out: how big it is, what a pixel is made of, and how the pixel
+// data is cut up and compressed. This is what tiffinfo shows, and what you want to know when an
+// imaging pipeline chokes on a file. Each numbered directory (IFD0, IFD1...) describes one image.
+//
+// Most of these tags have a default the spec says to assume when they're missing, which we fill
+// in, so a missing tag and one set to its default look the same. Ref: TIFF 6.0 section 8.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageStructure {
+    pub width: Option<u64>,
+    pub length: Option<u64>,
+    // One per sample (channel), though some writers only give one for all of them. Defaults to a
+    // single 1, since TIFF started out as a fax format.
+    pub bits_per_sample: Vec<u64>,
+    pub samples_per_pixel: u64,
+    // One per sample, like bits_per_sample. Defaults to unsigned integers.
+    pub sample_format: Vec<u64>,
+    pub compression: u64,
+    pub photometric_interpretation: Option<u64>,
+    pub planar_configuration: u64,
+    pub predictor: u64,
+    pub layout: Layout,
+}
+
+// Pixel data comes in strips (bands of whole rows) or tiles (rectangles), never both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Strips { rows_per_strip: Option<u64>, count: usize },
+    Tiles { width: u64, length: u64, count: usize },
+    // Neither, which happens in exif, where IFD0 describes an image stored elsewhere.
+    None,
+}
+
+impl ImageStructure {
+    // Read the structure tags from one of the numbered directories. The others don't describe
+    // images, so they'll come out as a 1 bit image of unknown size, with no pixel data.
+    pub fn read(exif: &Exif, directory: &Directory) -> Result<Self> {
+        let values = |tag: TagId| -> Result<Option<Vec<u64>>> {
+            match directory.entries_for(tag.id(), exif.duplicates()).first() {
+                Some(entry) => Ok(exif.value(entry)?.to_u64s()),
+                None => Ok(None)
+            }
+        };
+        let value = |tag: TagId| -> Result<Option<u64>> {
+            Ok(values(tag)?.and_then(|v| v.first().copied()))
+        };
+
+        let samples_per_pixel = value(TagId::SamplesPerPixel)?.unwrap_or(1);
+        let layout = match (values(TagId::TileOffsets)?, values(TagId::StripOffsets)?) {
+            (Some(offsets), _) => Layout::Tiles {
+                width: value(TagId::TileWidth)?.unwrap_or(0),
+                length: value(TagId::TileLength)?.unwrap_or(0),
+                count: offsets.len(),
+            },
+            (None, Some(offsets)) => Layout::Strips {
+                rows_per_strip: value(TagId::RowsPerStrip)?,
+                count: offsets.len(),
+            },
+            (None, None) => Layout::None,
+        };
+        Ok(ImageStructure {
+            width: value(TagId::ImageWidth)?,
+            length: value(TagId::ImageLength)?,
+            bits_per_sample: values(TagId::BitsPerSample)?.unwrap_or_else(|| vec![1]),
+            samples_per_pixel,
+            sample_format: values(TagId::SampleFormat)?.unwrap_or_else(|| vec![1]),
+            compression: value(TagId::Compression)?.unwrap_or(1),
+            photometric_interpretation: value(TagId::PhotometricInterpretation)?,
+            planar_configuration: value(TagId::PlanarConfiguration)?.unwrap_or(1),
+            predictor: value(TagId::Predictor)?.unwrap_or(1),
+            layout,
+        })
+    }
+
+    pub fn compression_name(&self) -> String {
+        code_name(COMPRESSIONS, self.compression)
+    }
+
+    pub fn photometric_interpretation_name(&self) -> Option<String> {
+        self.photometric_interpretation.map(|code| code_name(PHOTOMETRIC_INTERPRETATIONS, code))
+    }
+
+    pub fn planar_configuration_name(&self) -> String {
+        code_name(PLANAR_CONFIGURATIONS, self.planar_configuration)
+    }
+
+    pub fn predictor_name(&self) -> String {
+        code_name(PREDICTORS, self.predictor)
+    }
+
+    pub fn sample_format_names(&self) -> Vec<String> {
+        self.sample_format.iter().map(|&code| code_name(SAMPLE_FORMATS, code)).collect()
+    }
+}
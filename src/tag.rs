@@ -0,0 +1,270 @@
+use std::fmt;
+
+use crate::ifd::IfdKind;
+
+// Tag numbers are only unique within a directory's namespace. TIFF tags (IFD0, IFD1) and Exif IFD
+// tags were numbered so they don't collide, but the GPS and Interop directories start again from
+// 0. So to turn a number into a name we need to know what kind of directory it came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Namespace {
+    Main,
+    Gps,
+    Interop,
+}
+
+fn namespace(kind: IfdKind) -> Namespace {
+    match kind {
+        IfdKind::Ifd(_) | IfdKind::Exif | IfdKind::SubIfd(_) => Namespace::Main,
+        IfdKind::Gps => Namespace::Gps,
+        IfdKind::Interop => Namespace::Interop,
+    }
+}
+
+// Writing out every tag three times (enum variant, number, name) would be a good way to get them
+// out of sync, so this macro does it from one list. For each `Name = number, home;` line we get a
+// TagId::Name variant, and a row in TAGS tying it to its number, the directory the spec puts it in
+// (its "home"), and its name as a string, courtesy of stringify!.
+macro_rules! tags {
+    ($($name:ident = $id:expr, $home:expr;)*) => {
+        // A tag we know the name of, or Unknown with its number if we don't. Variants are named
+        // exactly as the Exif and TIFF specs name the tags, acronyms and all.
+        #[allow(clippy::upper_case_acronyms)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum TagId {
+            $($name,)*
+            Unknown(u16),
+        }
+
+        const TAGS: &[(TagId, u16, IfdKind, &str)] = &[
+            $((TagId::$name, $id, $home, stringify!($name)),)*
+        ];
+    };
+}
+
+// Sources: TIFF 6.0 section 8 and appendix A, https://www.exif.org/Exif2-2.PDF section 4.6, the
+// Exif 2.3 additions (lens and serial numbers, offset times), and GeoTIFF 1.1 (the Model* and
+// Geo* tags), http://docs.opengeospatial.org/is/19-008r4/19-008r4.html.
+const TIFF: IfdKind = IfdKind::Ifd(0);
+const EXIF: IfdKind = IfdKind::Exif;
+const GPS: IfdKind = IfdKind::Gps;
+const INTEROP: IfdKind = IfdKind::Interop;
+
+tags! {
+    // TIFF tags, found in IFD0 (and IFD1, for the thumbnail).
+    ImageWidth = 0x0100, TIFF;
+    ImageLength = 0x0101, TIFF;
+    BitsPerSample = 0x0102, TIFF;
+    Compression = 0x0103, TIFF;
+    PhotometricInterpretation = 0x0106, TIFF;
+    ImageDescription = 0x010e, TIFF;
+    Make = 0x010f, TIFF;
+    Model = 0x0110, TIFF;
+    StripOffsets = 0x0111, TIFF;
+    Orientation = 0x0112, TIFF;
+    SamplesPerPixel = 0x0115, TIFF;
+    RowsPerStrip = 0x0116, TIFF;
+    StripByteCounts = 0x0117, TIFF;
+    XResolution = 0x011a, TIFF;
+    YResolution = 0x011b, TIFF;
+    PlanarConfiguration = 0x011c, TIFF;
+    ResolutionUnit = 0x0128, TIFF;
+    TransferFunction = 0x012d, TIFF;
+    Software = 0x0131, TIFF;
+    DateTime = 0x0132, TIFF;
+    Artist = 0x013b, TIFF;
+    HostComputer = 0x013c, TIFF;
+    Predictor = 0x013d, TIFF;
+    WhitePoint = 0x013e, TIFF;
+    PrimaryChromaticities = 0x013f, TIFF;
+    TileWidth = 0x0142, TIFF;
+    TileLength = 0x0143, TIFF;
+    TileOffsets = 0x0144, TIFF;
+    TileByteCounts = 0x0145, TIFF;
+    SubIFDs = 0x014a, TIFF;
+    ExtraSamples = 0x0152, TIFF;
+    SampleFormat = 0x0153, TIFF;
+    JPEGInterchangeFormat = 0x0201, TIFF;
+    JPEGInterchangeFormatLength = 0x0202, TIFF;
+    YCbCrCoefficients = 0x0211, TIFF;
+    YCbCrSubSampling = 0x0212, TIFF;
+    YCbCrPositioning = 0x0213, TIFF;
+    ReferenceBlackWhite = 0x0214, TIFF;
+    XMLPacket = 0x02bc, TIFF;
+    Rating = 0x4746, TIFF;
+    RatingPercent = 0x4749, TIFF;
+    Copyright = 0x8298, TIFF;
+    ModelPixelScaleTag = 0x830e, TIFF;
+    IPTCNAA = 0x83bb, TIFF;
+    ModelTiepointTag = 0x8482, TIFF;
+    ModelTransformationTag = 0x85d8, TIFF;
+    ExifIFDPointer = 0x8769, TIFF;
+    GeoKeyDirectoryTag = 0x87af, TIFF;
+    GeoDoubleParamsTag = 0x87b0, TIFF;
+    GeoAsciiParamsTag = 0x87b1, TIFF;
+    GPSInfoIFDPointer = 0x8825, TIFF;
+    XPTitle = 0x9c9b, TIFF;
+    XPComment = 0x9c9c, TIFF;
+    XPAuthor = 0x9c9d, TIFF;
+    XPKeywords = 0x9c9e, TIFF;
+    XPSubject = 0x9c9f, TIFF;
+    PrintIM = 0xc4a5, TIFF;
+
+    // Exif IFD tags.
+    ExposureTime = 0x829a, EXIF;
+    FNumber = 0x829d, EXIF;
+    ExposureProgram = 0x8822, EXIF;
+    SpectralSensitivity = 0x8824, EXIF;
+    ISOSpeedRatings = 0x8827, EXIF;
+    OECF = 0x8828, EXIF;
+    SensitivityType = 0x8830, EXIF;
+    ExifVersion = 0x9000, EXIF;
+    DateTimeOriginal = 0x9003, EXIF;
+    DateTimeDigitized = 0x9004, EXIF;
+    OffsetTime = 0x9010, EXIF;
+    OffsetTimeOriginal = 0x9011, EXIF;
+    OffsetTimeDigitized = 0x9012, EXIF;
+    ComponentsConfiguration = 0x9101, EXIF;
+    CompressedBitsPerPixel = 0x9102, EXIF;
+    ShutterSpeedValue = 0x9201, EXIF;
+    ApertureValue = 0x9202, EXIF;
+    BrightnessValue = 0x9203, EXIF;
+    ExposureBiasValue = 0x9204, EXIF;
+    MaxApertureValue = 0x9205, EXIF;
+    SubjectDistance = 0x9206, EXIF;
+    MeteringMode = 0x9207, EXIF;
+    LightSource = 0x9208, EXIF;
+    Flash = 0x9209, EXIF;
+    FocalLength = 0x920a, EXIF;
+    SubjectArea = 0x9214, EXIF;
+    MakerNote = 0x927c, EXIF;
+    UserComment = 0x9286, EXIF;
+    SubSecTime = 0x9290, EXIF;
+    SubSecTimeOriginal = 0x9291, EXIF;
+    SubSecTimeDigitized = 0x9292, EXIF;
+    FlashpixVersion = 0xa000, EXIF;
+    ColorSpace = 0xa001, EXIF;
+    PixelXDimension = 0xa002, EXIF;
+    PixelYDimension = 0xa003, EXIF;
+    RelatedSoundFile = 0xa004, EXIF;
+    InteroperabilityIFDPointer = 0xa005, EXIF;
+    FlashEnergy = 0xa20b, EXIF;
+    SpatialFrequencyResponse = 0xa20c, EXIF;
+    FocalPlaneXResolution = 0xa20e, EXIF;
+    FocalPlaneYResolution = 0xa20f, EXIF;
+    FocalPlaneResolutionUnit = 0xa210, EXIF;
+    SubjectLocation = 0xa214, EXIF;
+    ExposureIndex = 0xa215, EXIF;
+    SensingMethod = 0xa217, EXIF;
+    FileSource = 0xa300, EXIF;
+    SceneType = 0xa301, EXIF;
+    CFAPattern = 0xa302, EXIF;
+    CustomRendered = 0xa401, EXIF;
+    ExposureMode = 0xa402, EXIF;
+    WhiteBalance = 0xa403, EXIF;
+    DigitalZoomRatio = 0xa404, EXIF;
+    FocalLengthIn35mmFilm = 0xa405, EXIF;
+    SceneCaptureType = 0xa406, EXIF;
+    GainControl = 0xa407, EXIF;
+    Contrast = 0xa408, EXIF;
+    Saturation = 0xa409, EXIF;
+    Sharpness = 0xa40a, EXIF;
+    DeviceSettingDescription = 0xa40b, EXIF;
+    SubjectDistanceRange = 0xa40c, EXIF;
+    ImageUniqueID = 0xa420, EXIF;
+    CameraOwnerName = 0xa430, EXIF;
+    BodySerialNumber = 0xa431, EXIF;
+    LensSpecification = 0xa432, EXIF;
+    LensMake = 0xa433, EXIF;
+    LensModel = 0xa434, EXIF;
+    LensSerialNumber = 0xa435, EXIF;
+    Gamma = 0xa500, EXIF;
+
+    // GPS IFD tags.
+    GPSVersionID = 0x0000, GPS;
+    GPSLatitudeRef = 0x0001, GPS;
+    GPSLatitude = 0x0002, GPS;
+    GPSLongitudeRef = 0x0003, GPS;
+    GPSLongitude = 0x0004, GPS;
+    GPSAltitudeRef = 0x0005, GPS;
+    GPSAltitude = 0x0006, GPS;
+    GPSTimeStamp = 0x0007, GPS;
+    GPSSatellites = 0x0008, GPS;
+    GPSStatus = 0x0009, GPS;
+    GPSMeasureMode = 0x000a, GPS;
+    GPSDOP = 0x000b, GPS;
+    GPSSpeedRef = 0x000c, GPS;
+    GPSSpeed = 0x000d, GPS;
+    GPSTrackRef = 0x000e, GPS;
+    GPSTrack = 0x000f, GPS;
+    GPSImgDirectionRef = 0x0010, GPS;
+    GPSImgDirection = 0x0011, GPS;
+    GPSMapDatum = 0x0012, GPS;
+    GPSDestLatitudeRef = 0x0013, GPS;
+    GPSDestLatitude = 0x0014, GPS;
+    GPSDestLongitudeRef = 0x0015, GPS;
+    GPSDestLongitude = 0x0016, GPS;
+    GPSDestBearingRef = 0x0017, GPS;
+    GPSDestBearing = 0x0018, GPS;
+    GPSDestDistanceRef = 0x0019, GPS;
+    GPSDestDistance = 0x001a, GPS;
+    GPSProcessingMethod = 0x001b, GPS;
+    GPSAreaInformation = 0x001c, GPS;
+    GPSDateStamp = 0x001d, GPS;
+    GPSDifferential = 0x001e, GPS;
+    GPSHPositioningError = 0x001f, GPS;
+
+    // Interoperability IFD tags.
+    InteroperabilityIndex = 0x0001, INTEROP;
+    InteroperabilityVersion = 0x0002, INTEROP;
+    RelatedImageFileFormat = 0x1000, INTEROP;
+    RelatedImageWidth = 0x1001, INTEROP;
+    RelatedImageLength = 0x1002, INTEROP;
+}
+
+impl TagId {
+    // Look a tag number up, given the kind of directory it was found in.
+    pub fn from_id(kind: IfdKind, id: u16) -> Self {
+        let ns = namespace(kind);
+        TAGS.iter()
+            .find(|(_, tag_id, home, _)| *tag_id == id && namespace(*home) == ns)
+            .map(|(tag, _, _, _)| *tag)
+            .unwrap_or(TagId::Unknown(id))
+    }
+
+    // Look a tag up by name, like "DateTimeOriginal". Useful for command line arguments.
+    pub fn from_name(name: &str) -> Option<Self> {
+        TAGS.iter().find(|(_, _, _, tag_name)| *tag_name == name).map(|(tag, _, _, _)| *tag)
+    }
+
+    fn row(&self) -> Option<&'static (TagId, u16, IfdKind, &'static str)> {
+        TAGS.iter().find(|(tag, _, _, _)| tag == self)
+    }
+
+    // The tag's number.
+    pub fn id(&self) -> u16 {
+        match self {
+            TagId::Unknown(id) => *id,
+            _ => self.row().map(|(_, id, _, _)| *id).unwrap_or(0)
+        }
+    }
+
+    // Which directory the spec says this tag lives in. Unknown tags don't have one.
+    pub fn home(&self) -> Option<IfdKind> {
+        self.row().map(|(_, _, home, _)| *home)
+    }
+
+    // The tag's name, or None for unknown tags.
+    pub fn name(&self) -> Option<&'static str> {
+        self.row().map(|(_, _, _, name)| *name)
+    }
+}
+
+// Tags display as their name, or their number in hex if we don't know it.
+impl fmt::Display for TagId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "Unknown(0x{:04x})", self.id())
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+use std::str;
+
+// The spec says ASCII values are 7-bit ASCII, but cameras and editing software happily write utf8
+// or Latin-1 (ISO 8859-1) into them, so we can't just bail when a byte is over 0x7f. We try utf8
+// first, since ASCII is a subset of it. If that fails it's probably Latin-1, and every byte is a
+// valid Latin-1 character which maps straight onto the first 256 unicode code points, so that
+// conversion can't fail.
+//
+// Most text is plain ASCII, and comes back borrowed from bytes, without a copy.
+pub fn decode_text(bytes: &[u8]) -> Cow<'_, str> {
+    match str::from_utf8(bytes) {
+        Ok(s) => repair_mojibake(s),
+        Err(_) => Cow::Owned(bytes.iter().map(|&b| b as char).collect())
+    }
+}
+
+// A very common kind of mojibake: utf8 text that some program read as Latin-1 and wrote back out
+// as utf8, so "é" (c3 a9) turns into "Ã©" (c3 83 c2 a9). If every character fits in a single byte,
+// and those bytes happen to be valid (non-ASCII) utf8 themselves, that's almost certainly what
+// happened, and we can undo it.
+fn repair_mojibake(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() || s.chars().any(|c| c as u32 > 0xff) {
+        return Cow::Borrowed(s);
+    }
+    let bytes: Vec<u8> = s.chars().map(|c| c as u8).collect();
+    String::from_utf8(bytes).map_or(Cow::Borrowed(s), Cow::Owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_utf8_and_falls_back_to_latin1() {
+        assert_eq!(decode_text(b"Canon"), "Canon");
+        assert_eq!(decode_text("Café".as_bytes()), "Café");
+        assert_eq!(decode_text("東京".as_bytes()), "東京");
+        // Latin-1 "Café", which isn't valid utf8.
+        assert_eq!(decode_text(b"Caf\xe9"), "Café");
+        assert_eq!(decode_text(b"\xa9 2008 J\xf6rg"), "© 2008 Jörg");
+        assert_eq!(decode_text(b""), "");
+    }
+
+    #[test]
+    fn repairs_double_encoded_utf8() {
+        assert_eq!(decode_text("CafÃ©".as_bytes()), "Café");
+        assert_eq!(decode_text("Â© 2008 JÃ¶rg".as_bytes()), "© 2008 Jörg");
+        assert_eq!(repair_mojibake("CafÃ©"), "Café");
+    }
+
+    #[test]
+    fn leaves_text_that_is_not_mojibake_alone() {
+        // Latin-1 characters that don't make valid utf8 when they're turned back into bytes.
+        assert_eq!(repair_mojibake("Café"), "Café");
+        assert_eq!(repair_mojibake("Ãx"), "Ãx");
+        // Anything outside Latin-1 can't have come from reading bytes as Latin-1.
+        assert_eq!(repair_mojibake("CafÃ© €"), "CafÃ© €");
+        assert_eq!(repair_mojibake("plain ASCII"), "plain ASCII");
+    }
+
+    #[test]
+    fn plain_text_is_borrowed() {
+        assert!(matches!(decode_text(b"Canon"), Cow::Borrowed(_)));
+        assert!(matches!(decode_text("Café".as_bytes()), Cow::Borrowed(_)));
+        assert!(matches!(decode_text(b"Caf\xe9"), Cow::Owned(_)));
+    }
+}
@@ -0,0 +1,230 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::error::{ExifError, Result};
+
+// TIFF files (and so exif blocks) can be written in either byte order, and say which in the first
+// two bytes of their header: "II" for Intel, little endian, or "MM" for Motorola, big endian.
+// Every multi-byte number after that is stored in that order, so we need to carry it around.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl ByteOrder {
+    // These all expect exactly the right number of bytes, like the from_*_bytes functions they
+    // wrap. Callers are responsible for slicing, and for checking there's enough to slice. The
+    // *_at versions below do that checking for you, and are what you want when reading from an
+    // offset that came out of the file.
+    pub fn read_u16(&self, bytes: &[u8]) -> u16 {
+        let mut array: [u8; 2] = [0; 2];
+        array.copy_from_slice(bytes);
+        match self {
+            ByteOrder::LittleEndian => u16::from_le_bytes(array),
+            ByteOrder::BigEndian => u16::from_be_bytes(array),
+        }
+    }
+
+    pub fn read_u32(&self, bytes: &[u8]) -> u32 {
+        let mut array: [u8; 4] = [0; 4];
+        array.copy_from_slice(bytes);
+        match self {
+            ByteOrder::LittleEndian => u32::from_le_bytes(array),
+            ByteOrder::BigEndian => u32::from_be_bytes(array),
+        }
+    }
+
+    pub fn read_u64(&self, bytes: &[u8]) -> u64 {
+        let mut array: [u8; 8] = [0; 8];
+        array.copy_from_slice(bytes);
+        match self {
+            ByteOrder::LittleEndian => u64::from_le_bytes(array),
+            ByteOrder::BigEndian => u64::from_be_bytes(array),
+        }
+    }
+
+    // Read a number from buf at offset, or say what we were trying to read if it doesn't fit.
+    pub fn u16_at(&self, buf: &[u8], offset: usize, what: &'static str) -> Result<u16> {
+        let end = offset.checked_add(2).ok_or(ExifError::InvalidOffset(what))?;
+        buf.get(offset..end).map(|bytes| self.read_u16(bytes)).ok_or(ExifError::TruncatedBuffer(what))
+    }
+
+    pub fn u32_at(&self, buf: &[u8], offset: usize, what: &'static str) -> Result<u32> {
+        let end = offset.checked_add(4).ok_or(ExifError::InvalidOffset(what))?;
+        buf.get(offset..end).map(|bytes| self.read_u32(bytes)).ok_or(ExifError::TruncatedBuffer(what))
+    }
+
+    pub fn u64_at(&self, buf: &[u8], offset: usize, what: &'static str) -> Result<u64> {
+        let end = offset.checked_add(8).ok_or(ExifError::InvalidOffset(what))?;
+        buf.get(offset..end).map(|bytes| self.read_u64(bytes)).ok_or(ExifError::TruncatedBuffer(what))
+    }
+
+    // The other way round: a number back into the bytes it was read from.
+    pub fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+
+    pub fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+
+    pub fn u64_bytes(&self, value: u64) -> [u8; 8] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+}
+
+// An offset or count from the file, as something we can index with. A BigTIFF offset can be
+// bigger than a 32 bit usize can hold, and `as` would quietly chop off the top half.
+pub fn to_usize(value: u64, what: &'static str) -> Result<usize> {
+    usize::try_from(value).map_err(|_| ExifError::InvalidOffset(what))
+}
+
+// The length bytes of buf starting at offset, if they're all there. Offsets and counts come out of
+// the file as u32s, so a broken (or malicious) one can be anything up to u32::MAX, and offset +
+// length can overflow. Where usize is 64 bits that can't happen with a u32 of each, but usize is
+// 32 bits on some targets we'd like to run on, and there an overflow would quietly wrap around to
+// a small number that points back into the buffer. So we check the addition, and treat overflow
+// like any other offset that's out of range.
+pub fn slice_at(buf: &[u8], offset: usize, length: usize) -> Option<&[u8]> {
+    buf.get(offset..offset.checked_add(length)?)
+}
+
+impl fmt::Display for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ByteOrder::LittleEndian => write!(f, "little endian (II)"),
+            ByteOrder::BigEndian => write!(f, "big endian (MM)"),
+        }
+    }
+}
+
+// Plain TIFF stores offsets and counts as 32 bits, which tops out at 4GB. BigTIFF is the same idea
+// with 64 bits, for the huge scans and scientific images that outgrew that. Everything that holds
+// an offset or count gets wider, so directories and their entries are laid out differently.
+// Ref: https://www.awaresystems.be/imaging/tiff/bigtiff.html
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TiffVariant {
+    #[default]
+    Classic,
+    Big,
+}
+
+impl TiffVariant {
+    // How big an offset (and so the value_offset field of an entry) is.
+    pub fn offset_size(&self) -> usize {
+        match self {
+            TiffVariant::Classic => 4,
+            TiffVariant::Big => 8,
+        }
+    }
+
+    // How big the entry count at the start of a directory is.
+    pub fn count_size(&self) -> usize {
+        match self {
+            TiffVariant::Classic => 2,
+            TiffVariant::Big => 8,
+        }
+    }
+
+    // How big one directory entry is: tag and type, then a count and value_offset of
+    // offset_size each.
+    pub fn entry_size(&self) -> usize {
+        4 + 2 * self.offset_size()
+    }
+
+    // Read an offset (or entry count, in BigTIFF) of this variant's size from buf at offset.
+    pub fn offset_at(&self, byte_order: ByteOrder, buf: &[u8], offset: usize, what: &'static str) -> Result<u64> {
+        match self {
+            TiffVariant::Classic => byte_order.u32_at(buf, offset, what).map(u64::from),
+            TiffVariant::Big => byte_order.u64_at(buf, offset, what),
+        }
+    }
+}
+
+// The 8 byte TIFF header, https://www.exif.org/Exif2-2.PDF, 4.5.2:
+// 2 bytes of byte order, 2 bytes holding 42 (in that byte order, so we can check we got it
+// right), and a 4 byte offset to the first IFD. Like every other offset in here, that one is
+// counted from the start of the header, not the start of the file.
+//
+// BigTIFF's is 16 bytes: the byte order, 43 instead of 42, 2 bytes giving the size of an offset
+// (always 8), 2 bytes of 0, and an 8 byte offset to the first IFD.
+pub struct TiffHeader {
+    pub byte_order: ByteOrder,
+    pub variant: TiffVariant,
+    pub ifd0_offset: u64,
+}
+
+impl TiffHeader {
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 8 {
+            return Err(ExifError::TruncatedBuffer("TIFF header"));
+        }
+        let byte_order = match &buf[..2] {
+            b"II" => ByteOrder::LittleEndian,
+            b"MM" => ByteOrder::BigEndian,
+            _ => return Err(ExifError::BadMarker("TIFF byte order")),
+        };
+        match byte_order.read_u16(&buf[2..4]) {
+            42 => Ok(TiffHeader {
+                byte_order,
+                variant: TiffVariant::Classic,
+                ifd0_offset: byte_order.read_u32(&buf[4..8]) as u64,
+            }),
+            43 => {
+                if byte_order.u16_at(buf, 4, "BigTIFF header")? != 8 || byte_order.u16_at(buf, 6, "BigTIFF header")? != 0 {
+                    return Err(ExifError::BadMarker("BigTIFF offset size, it should be 8"));
+                }
+                Ok(TiffHeader {
+                    byte_order,
+                    variant: TiffVariant::Big,
+                    ifd0_offset: byte_order.u64_at(buf, 8, "BigTIFF header")?,
+                })
+            },
+            _ => Err(ExifError::BadMarker("TIFF magic number, it should be 42 (or 43 for BigTIFF)")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_at_checks_the_range() {
+        let buf = [1, 2, 3, 4];
+        assert_eq!(slice_at(&buf, 1, 2), Some(&buf[1..3]));
+        assert_eq!(slice_at(&buf, 4, 0), Some(&buf[4..]));
+        assert_eq!(slice_at(&buf, 3, 2), None);
+        assert_eq!(slice_at(&buf, 5, 0), None);
+        assert_eq!(slice_at(&buf, usize::MAX, 1), None);
+        assert_eq!(slice_at(&buf, 1, usize::MAX), None);
+        assert_eq!(slice_at(&buf, u32::MAX as usize, u32::MAX as usize), None);
+    }
+
+    #[test]
+    fn reads_past_the_end_are_errors() {
+        let buf = [0; 8];
+        assert!(ByteOrder::LittleEndian.u16_at(&buf, 7, "test").is_err());
+        assert!(ByteOrder::BigEndian.u32_at(&buf, usize::MAX - 1, "test").is_err());
+        assert!(ByteOrder::LittleEndian.u64_at(&buf, usize::MAX, "test").is_err());
+        assert_eq!(ByteOrder::BigEndian.u64_at(&buf, 0, "test").ok(), Some(0));
+    }
+
+    #[test]
+    fn to_usize_refuses_what_does_not_fit() {
+        assert_eq!(to_usize(u32::MAX as u64, "test").ok(), Some(u32::MAX as usize));
+        if usize::BITS < 64 {
+            assert!(to_usize(u64::MAX, "test").is_err());
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::error::{ExifError, Result};
+use crate::ifd::IFD;
+use crate::rational::{Rational, SRational};
+use crate::text::decode_text;
+use crate::tiff::{slice_at, to_usize, ByteOrder};
+
+// A tag's value, decoded according to its type. Every type is stored as a list of `count` values,
+// even though most tags only have one, so every variant holds a Vec. ASCII is the exception: its
+// count is the number of bytes (NUL terminator included), and what you want is the string.
+// Ref: https://www.exif.org/Exif2-2.PDF, 4.6.2, and TIFF 6.0 section 2 for FLOAT and DOUBLE.
+//
+// Bytes and text are the same in the file as they are here, so those borrow from the buffer the
+// value was decoded from instead of copying it (maker notes run to kilobytes). Numbers have to be
+// put in our byte order, so they're always copied. into_owned cuts a value loose from the buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagValue<'a> {
+    Byte(Cow<'a, [u8]>),
+    Ascii(Cow<'a, str>),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<Rational>),
+    SByte(Vec<i8>),
+    // Raw bytes, which mean whatever the tag says they mean.
+    Undefined(Cow<'a, [u8]>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    SRational(Vec<SRational>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    // BigTIFF's 64 bit integers.
+    Long8(Vec<u64>),
+    SLong8(Vec<i64>),
+}
+
+// How many bytes one value of each type takes up, or None for types we don't know. 13 is IFD, a
+// LONG that's an offset to another directory, which later TIFF specs added. BigTIFF adds 16 and 17,
+// LONG8 and SLONG8, and 18, IFD8, which is to LONG8 what IFD is to LONG.
+pub fn type_size(tag_type: u16) -> Option<usize> {
+    match tag_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 | 16 | 17 | 18 => Some(8),
+        _ => None
+    }
+}
+
+impl<'a> TagValue<'a> {
+    // Decode an entry's value. `tiff` starts at the tiff header, since that's what offsets are
+    // relative to.
+    //
+    // The inline rule: value_offset is only an offset if the value doesn't fit in it. If the whole
+    // value takes 4 bytes or less (a SHORT or two, up to four BYTEs, a short string), it's stored
+    // right there in the value_offset bytes, left-justified. We parsed those bytes into a number in
+    // the file's byte order, so we turn them back into bytes the same way to get the value out.
+    // BigTIFF's value_offset is 8 bytes, so there it's 8 bytes or less. Those are the only values
+    // that can't borrow from tiff.
+    pub fn decode(entry: &IFD, tiff: &'a [u8], byte_order: ByteOrder) -> Result<Self> {
+        let size = type_size(entry.tag_type).ok_or(ExifError::UnsupportedType(entry.tag_type))?;
+        let count = to_usize(entry.count, "tag value")?;
+        // A count near u32::MAX times 8 bytes is more than a 32 bit usize can hold, and would
+        // wrap around to something small and innocent looking, so that's checked too.
+        let length = size.checked_mul(count).ok_or(ExifError::InvalidOffset("tag value"))?;
+        let inline = entry.inline_bytes(byte_order);
+        let data = if length <= inline.len() {
+            Cow::Owned(inline[..length].to_vec())
+        } else {
+            Cow::Borrowed(slice_at(tiff, to_usize(entry.value_offset, "tag value")?, length).ok_or(ExifError::InvalidOffset("tag value"))?)
+        };
+        Ok(TagValue::from_bytes(entry.tag_type, data, byte_order))
+    }
+
+    // The same value, with its own copy of anything it was borrowing, so it can outlive the
+    // buffer (to be written into another file, say).
+    pub fn into_owned(self) -> TagValue<'static> {
+        match self {
+            TagValue::Byte(v) => TagValue::Byte(Cow::Owned(v.into_owned())),
+            TagValue::Ascii(s) => TagValue::Ascii(Cow::Owned(s.into_owned())),
+            TagValue::Undefined(v) => TagValue::Undefined(Cow::Owned(v.into_owned())),
+            TagValue::Short(v) => TagValue::Short(v),
+            TagValue::Long(v) => TagValue::Long(v),
+            TagValue::Rational(v) => TagValue::Rational(v),
+            TagValue::SByte(v) => TagValue::SByte(v),
+            TagValue::SShort(v) => TagValue::SShort(v),
+            TagValue::SLong(v) => TagValue::SLong(v),
+            TagValue::SRational(v) => TagValue::SRational(v),
+            TagValue::Float(v) => TagValue::Float(v),
+            TagValue::Double(v) => TagValue::Double(v),
+            TagValue::Long8(v) => TagValue::Long8(v),
+            TagValue::SLong8(v) => TagValue::SLong8(v),
+        }
+    }
+
+    // The value as a list of unsigned integers, for tags the spec lets be SHORT or LONG (image
+    // sizes and offsets, mostly), where we don't care which one the writer picked. None for
+    // anything else.
+    pub fn to_u64s(&self) -> Option<Vec<u64>> {
+        match self {
+            TagValue::Byte(v) => Some(v.iter().map(|&v| v as u64).collect()),
+            TagValue::Short(v) => Some(v.iter().map(|&v| v as u64).collect()),
+            TagValue::Long(v) => Some(v.iter().map(|&v| v as u64).collect()),
+            TagValue::Long8(v) => Some(v.clone()),
+            _ => None
+        }
+    }
+
+    // The other way round from decode: the type, count and bytes to write for this value, in the
+    // given byte order. Strings get their NUL terminator back.
+    pub fn encode(&self, byte_order: ByteOrder) -> (u16, u64, Vec<u8>) {
+        let u16s = |v: u16| byte_order.u16_bytes(v);
+        let u32s = |v: u32| byte_order.u32_bytes(v);
+        let u64s = |v: u64| byte_order.u64_bytes(v);
+        let (tag_type, count, data) = match self {
+            TagValue::Byte(v) => (1, v.len(), v.to_vec()),
+            TagValue::Ascii(s) => {
+                let mut data = s.as_bytes().to_vec();
+                data.push(0);
+                (2, data.len(), data)
+            },
+            TagValue::Short(v) => (3, v.len(), flatten(v.iter().copied(), u16s)),
+            TagValue::Long(v) => (4, v.len(), flatten(v.iter().copied(), u32s)),
+            TagValue::Rational(v) => (5, v.len(), flatten(v.iter().flat_map(|r| [r.numerator(), r.denominator()]), u32s)),
+            TagValue::SByte(v) => (6, v.len(), v.iter().map(|&b| b as u8).collect()),
+            TagValue::Undefined(v) => (7, v.len(), v.to_vec()),
+            TagValue::SShort(v) => (8, v.len(), flatten(v.iter().map(|&v| v as u16), u16s)),
+            TagValue::SLong(v) => (9, v.len(), flatten(v.iter().map(|&v| v as u32), u32s)),
+            TagValue::SRational(v) => (10, v.len(), flatten(v.iter().flat_map(|r| [r.numerator() as u32, r.denominator() as u32]), u32s)),
+            TagValue::Float(v) => (11, v.len(), flatten(v.iter().map(|v| v.to_bits()), u32s)),
+            TagValue::Double(v) => (12, v.len(), flatten(v.iter().map(|v| v.to_bits()), u64s)),
+            TagValue::Long8(v) => (16, v.len(), flatten(v.iter().copied(), u64s)),
+            TagValue::SLong8(v) => (17, v.len(), flatten(v.iter().map(|&v| v as u64), u64s)),
+        };
+        (tag_type, count as u64, data)
+    }
+
+    // Turn the raw bytes of a value into the right variant. `data` has to be exactly count values
+    // long, which decode makes sure of.
+    fn from_bytes(tag_type: u16, data: Cow<'a, [u8]>, byte_order: ByteOrder) -> Self {
+        let u16s = || data.chunks(2).map(|c| byte_order.read_u16(c));
+        let u32s = || data.chunks(4).map(|c| byte_order.read_u32(c));
+        let u64s = || data.chunks(8).map(|c| byte_order.read_u64(c));
+        match tag_type {
+            1 => TagValue::Byte(data),
+            2 => {
+                // Strings are NUL terminated (and sometimes NUL padded), which we don't want to
+                // see. Some writers forget the terminator, which is fine too.
+                let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                TagValue::Ascii(match data {
+                    Cow::Borrowed(data) => decode_text(&data[..end]),
+                    Cow::Owned(data) => Cow::Owned(decode_text(&data[..end]).into_owned()),
+                })
+            },
+            3 => TagValue::Short(u16s().collect()),
+            4 | 13 => TagValue::Long(u32s().collect()),
+            5 => TagValue::Rational(pairs(u32s().collect()).into_iter().map(|(n, d)| Rational::new(n, d)).collect()),
+            6 => TagValue::SByte(data.iter().map(|&b| b as i8).collect()),
+            8 => TagValue::SShort(u16s().map(|v| v as i16).collect()),
+            9 => TagValue::SLong(u32s().map(|v| v as i32).collect()),
+            10 => TagValue::SRational(pairs(u32s().map(|v| v as i32).collect()).into_iter().map(|(n, d)| SRational::new(n, d)).collect()),
+            11 => TagValue::Float(u32s().map(f32::from_bits).collect()),
+            12 => TagValue::Double(u64s().map(f64::from_bits).collect()),
+            16 | 18 => TagValue::Long8(u64s().collect()),
+            17 => TagValue::SLong8(u64s().map(|v| v as i64).collect()),
+            // 7, UNDEFINED, and anything type_size let through that we don't know better about.
+            _ => TagValue::Undefined(data),
+        }
+    }
+}
+
+// Numbers into bytes, one after another, for encode.
+fn flatten<T, const N: usize>(values: impl Iterator<Item = T>, bytes: impl Fn(T) -> [u8; N]) -> Vec<u8> {
+    values.flat_map(bytes).collect()
+}
+
+// So text and plain numbers can be handed straight to Exif::set.
+impl<'a> From<&'a str> for TagValue<'a> {
+    fn from(s: &'a str) -> Self {
+        TagValue::Ascii(Cow::Borrowed(s))
+    }
+}
+
+impl From<String> for TagValue<'static> {
+    fn from(s: String) -> Self {
+        TagValue::Ascii(Cow::Owned(s))
+    }
+}
+
+impl From<u16> for TagValue<'static> {
+    fn from(v: u16) -> Self {
+        TagValue::Short(vec![v])
+    }
+}
+
+impl From<u32> for TagValue<'static> {
+    fn from(v: u32) -> Self {
+        TagValue::Long(vec![v])
+    }
+}
+
+impl From<Rational> for TagValue<'static> {
+    fn from(v: Rational) -> Self {
+        TagValue::Rational(vec![v])
+    }
+}
+
+// Rationals are stored as two numbers one after the other, numerator then denominator.
+fn pairs<T: Copy>(values: Vec<T>) -> Vec<(T, T)> {
+    values.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+// Byte values can be enormous (maker notes run to kilobytes), so past this many we just show the
+// start, and how long the whole thing is.
+const MAX_DISPLAY_BYTES: usize = 16;
+
+// Default formatter for TagValue, so we can println!("{}", value). Lists are comma separated, and
+// rationals are shown as fractions.
+impl fmt::Display for TagValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagValue::Byte(v) | TagValue::Undefined(v) => {
+                let hex = v.iter().take(MAX_DISPLAY_BYTES).map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" ");
+                if v.len() > MAX_DISPLAY_BYTES {
+                    write!(f, "{} ... ({} bytes)", hex, v.len())
+                } else {
+                    write!(f, "{}", hex)
+                }
+            },
+            TagValue::Ascii(s) => write!(f, "'{}'", s),
+            TagValue::Short(v) => write!(f, "{}", join(v)),
+            TagValue::Long(v) => write!(f, "{}", join(v)),
+            TagValue::Rational(v) => write!(f, "{}", join(v)),
+            TagValue::SByte(v) => write!(f, "{}", join(v)),
+            TagValue::SShort(v) => write!(f, "{}", join(v)),
+            TagValue::SLong(v) => write!(f, "{}", join(v)),
+            TagValue::SRational(v) => write!(f, "{}", join(v)),
+            TagValue::Float(v) => write!(f, "{}", join(v)),
+            TagValue::Double(v) => write!(f, "{}", join(v)),
+            TagValue::Long8(v) => write!(f, "{}", join(v)),
+            TagValue::SLong8(v) => write!(f, "{}", join(v)),
+        }
+    }
+}
+
+fn join<T: fmt::Display>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiff::TiffVariant;
+
+    const LE: ByteOrder = ByteOrder::LittleEndian;
+
+    fn entry(tag_type: u16, count: u64, value_offset: u64) -> IFD {
+        IFD { tag: 0x010f, tag_type, count, value_offset, variant: TiffVariant::Classic }
+    }
+
+    #[test]
+    fn decodes_inline_and_out_of_line_values() {
+        let tiff = b"\0\0\0\0Canon\0";
+        assert_eq!(TagValue::decode(&entry(2, 6, 4), tiff, LE).unwrap(), TagValue::Ascii(Cow::Borrowed("Canon")));
+        assert_eq!(TagValue::decode(&entry(3, 2, 0x0002_0001), tiff, LE).unwrap(), TagValue::Short(vec![1, 2]));
+        assert!(matches!(TagValue::decode(&entry(7, 5, 4), tiff, LE).unwrap(), TagValue::Undefined(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn refuses_values_outside_the_buffer() {
+        let tiff = [0; 16];
+        for (tag_type, count, value_offset) in [
+            (1, 8, 12),
+            (1, 5, u32::MAX as u64),
+            (1, u32::MAX as u64, 0),
+            (5, u32::MAX as u64, u32::MAX as u64),
+            (12, u64::MAX, 0),
+            (1, 5, u64::MAX),
+            (16, u64::MAX / 4, 8),
+        ] {
+            assert!(TagValue::decode(&entry(tag_type, count, value_offset), &tiff, LE).is_err(), "{} {} {}", tag_type, count, value_offset);
+        }
+    }
+
+    #[test]
+    fn refuses_types_we_do_not_know() {
+        assert!(TagValue::decode(&entry(0, 1, 0), &[], LE).is_err());
+        assert!(TagValue::decode(&entry(u16::MAX, 1, 0), &[], LE).is_err());
+    }
+}
@@ -0,0 +1,576 @@
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use crate::confidence::Evidence;
+use crate::error::{ExifError, Result};
+use crate::filetype::FileType;
+use crate::ifd::IfdKind;
+use crate::jpeg::{APP1, EXIF_SIGNATURE};
+use crate::tag::TagId;
+use crate::tiff::{ByteOrder, TiffVariant};
+use crate::value::{type_size, TagValue};
+use crate::Exif;
+
+// Writing exif back out. The hard part isn't encoding values, it's offsets: values bigger than 4
+// bytes, sub-directories, the thumbnail, and (worst of all) maker notes are all found by offsets
+// from the tiff header, and moving any of them means finding and fixing everything that points at
+// them. Maker notes are the real problem, since they're vendor blobs that often hold offsets of
+// their own, which we can't see to fix.
+//
+// So we don't move anything we don't have to. The original tiff data stays exactly where it is,
+// and every directory that changes is written out again after it, with its new values after that.
+// Entries that didn't change keep their value_offset bytes verbatim, so they still point at their
+// values in the original data, and so do maker notes and the thumbnail. The only offsets we fix
+// are the ones that point at directories we moved: the pointer tags in their parents, the next
+// directory offset before them in the chain, and the header's offset of IFD0. Moving a directory
+// changes its parent, so that gets moved too, and so on up to IFD0.
+//
+// That costs some space, since the old copy of a changed directory is left behind, unreferenced.
+// We zero those, and the old values of anything changed or removed, so removing a tag (GPS, say)
+// really does take it out of the file.
+
+// A change to make.
+pub enum Edit {
+    // Set a tag in a directory, replacing any entries it already has there. The directory is
+    // created if it's one of the sub-directories and it doesn't exist yet.
+    Set(IfdKind, TagId, TagValue<'static>),
+    // Remove a tag from a directory, every entry of it if it's there more than once. Removing a
+    // pointer tag removes the directory it points at.
+    Remove(IfdKind, TagId),
+    // Remove one of the sub-directories, along with any of its own, and the pointer to it.
+    RemoveDirectory(IfdKind),
+}
+
+// Which tag in which directory points at each sub-directory we know how to write.
+const POINTERS: &[(IfdKind, TagId, IfdKind)] = &[
+    (IfdKind::Ifd(0), TagId::ExifIFDPointer, IfdKind::Exif),
+    (IfdKind::Ifd(0), TagId::GPSInfoIFDPointer, IfdKind::Gps),
+    (IfdKind::Exif, TagId::InteroperabilityIFDPointer, IfdKind::Interop),
+];
+
+// Tags that hold offsets to things we don't move (image data, the thumbnail, raw SubIFDs). Their
+// values only make sense where they are, so they can't be set or removed. The pointer tags above
+// are looked after for you.
+const MANAGED: &[TagId] = &[
+    TagId::StripOffsets,
+    TagId::TileOffsets,
+    TagId::JPEGInterchangeFormat,
+    TagId::SubIFDs,
+    TagId::ExifIFDPointer,
+    TagId::GPSInfoIFDPointer,
+    TagId::InteroperabilityIFDPointer,
+];
+
+// Tags that describe one file's picture, how it's encoded and how big, rather than the photo, and
+// so would be wrong in any other file. Maker notes are in here too, since they're full of offsets
+// into the file they came from. See copy_edits.
+const FILE_SPECIFIC: &[TagId] = &[
+    TagId::ImageWidth,
+    TagId::ImageLength,
+    TagId::BitsPerSample,
+    TagId::Compression,
+    TagId::PhotometricInterpretation,
+    TagId::SamplesPerPixel,
+    TagId::RowsPerStrip,
+    TagId::StripByteCounts,
+    TagId::PlanarConfiguration,
+    TagId::Predictor,
+    TagId::TileWidth,
+    TagId::TileLength,
+    TagId::TileByteCounts,
+    TagId::ExtraSamples,
+    TagId::SampleFormat,
+    TagId::JPEGInterchangeFormatLength,
+    TagId::YCbCrSubSampling,
+    TagId::ComponentsConfiguration,
+    TagId::CompressedBitsPerPixel,
+    TagId::PixelXDimension,
+    TagId::PixelYDimension,
+    TagId::ModelPixelScaleTag,
+    TagId::ModelTiepointTag,
+    TagId::ModelTransformationTag,
+    TagId::GeoKeyDirectoryTag,
+    TagId::GeoDoubleParamsTag,
+    TagId::GeoAsciiParamsTag,
+    TagId::MakerNote,
+];
+
+// An entry, as it'll be written.
+struct Entry {
+    tag: u16,
+    tag_type: u16,
+    count: u32,
+    value: Value,
+}
+
+enum Value {
+    // The original value_offset bytes: the value itself if it's small, or where it is in the
+    // original data, which stays put.
+    Kept([u8; 4]),
+    // A new value, which goes in the entry if it fits, or after the directory if it doesn't.
+    New(Vec<u8>),
+    // The offset of another directory, which we only know once it's been written.
+    Pointer(IfdKind),
+}
+
+impl Entry {
+    // Where a kept value is in the original data, if it isn't stored inline.
+    fn old_data(&self, byte_order: ByteOrder) -> Option<Range<usize>> {
+        let offset = match self.value {
+            Value::Kept(field) => byte_order.read_u32(&field) as usize,
+            _ => return None
+        };
+        let length = type_size(self.tag_type)?.checked_mul(self.count as usize)?;
+        if length <= 4 {
+            return None;
+        }
+        Some(offset..offset.checked_add(length)?)
+    }
+}
+
+// A directory, as it'll be written.
+struct Table {
+    kind: IfdKind,
+    entries: Vec<Entry>,
+    // Where it was in the original data, if it was there.
+    old: Option<Range<usize>>,
+    // Whether it needs writing out again.
+    dirty: bool,
+}
+
+// The edits that would copy from's metadata into another file: every tag in IFD0 and the Exif, GPS
+// and Interop directories, apart from the ones that only make sense in from itself (the offsets we
+// look after, and FILE_SPECIFIC). The thumbnail's IFD1 is about the thumbnail, so that stays
+// behind too. A tag that's in there more than once is copied as the first of them, and one whose
+// value we can't read is skipped, since there's nothing to copy.
+pub fn copy_edits(from: &Exif) -> Vec<Edit> {
+    let copied = |kind: IfdKind| matches!(kind, IfdKind::Ifd(0) | IfdKind::Exif | IfdKind::Gps | IfdKind::Interop);
+    let mut edits = Vec::new();
+    let mut seen = Vec::new();
+    for directory in from.directories().filter(|directory| copied(directory.kind)) {
+        for entry in &directory.entries {
+            let tag = entry.tag_id(directory.kind);
+            if MANAGED.contains(&tag) || FILE_SPECIFIC.contains(&tag) || seen.contains(&(directory.kind, tag)) {
+                continue;
+            }
+            seen.push((directory.kind, tag));
+            if let Ok(value) = from.value(entry) {
+                edits.push(Edit::Set(directory.kind, tag, value.into_owned()));
+            }
+        }
+    }
+    edits
+}
+
+// Make the changes, and hand back the whole file with them in.
+pub fn rewrite(exif: &Exif, edits: &[Edit]) -> Result<Vec<u8>> {
+    check_writable(exif)?;
+    let byte_order = exif.byte_order();
+    let mut tables = read_tables(exif);
+    // Old bytes to zero.
+    let mut erase = Vec::new();
+    for edit in edits {
+        match edit {
+            Edit::Set(kind, tag, value) => set(&mut tables, *kind, *tag, value, byte_order, &mut erase)?,
+            Edit::Remove(kind, tag) => remove(&mut tables, *kind, *tag, byte_order, &mut erase)?,
+            Edit::RemoveDirectory(kind) => remove_directory(&mut tables, *kind, byte_order, &mut erase)?,
+        }
+    }
+    mark_parents(&mut tables);
+
+    let mut tiff = exif.tiff().to_vec();
+    for table in tables.iter().filter(|table| table.dirty) {
+        erase.extend(table.old.clone());
+    }
+    for range in erase {
+        let range = range.start.min(tiff.len())..range.end.min(tiff.len());
+        tiff[range].iter_mut().for_each(|b| *b = 0);
+    }
+
+    // Children before parents, and the end of the chain before the start, so every offset we need
+    // is known by the time we write it.
+    let mut offsets: Vec<(IfdKind, usize)> = tables.iter()
+        .filter(|table| !table.dirty)
+        .filter_map(|table| Some((table.kind, table.old.as_ref()?.start)))
+        .collect();
+    tables.sort_by_key(|table| match table.kind {
+        IfdKind::Interop => (0, 0),
+        IfdKind::Exif => (1, 0),
+        IfdKind::Gps => (2, 0),
+        IfdKind::Ifd(n) => (3, usize::MAX - n),
+        IfdKind::SubIfd(n) => (4, n),
+    });
+    for table in tables.iter().filter(|table| table.dirty) {
+        let next = match table.kind {
+            IfdKind::Ifd(n) => offset_of(&offsets, IfdKind::Ifd(n + 1)).unwrap_or(0),
+            _ => 0
+        };
+        let offset = write_table(&mut tiff, table, &offsets, next, byte_order)?;
+        offsets.push((table.kind, offset));
+    }
+    if tables.iter().any(|table| table.dirty && table.kind == IfdKind::Ifd(0)) {
+        let ifd0 = offset_of(&offsets, IfdKind::Ifd(0)).ok_or(ExifError::InvalidOffset("IFD0"))?;
+        tiff[4..8].copy_from_slice(&byte_order.u32_bytes(ifd0 as u32));
+    }
+    splice(exif, &tiff)
+}
+
+// We can write plain tiff data that we found by following the file's structure, in the formats
+// where we know how to put it back. BigTIFF would need its own entry layout, and exif we found by
+// scanning isn't somewhere we could splice a new copy in.
+fn check_writable(exif: &Exif) -> Result<()> {
+    if exif.variant() == TiffVariant::Big {
+        return Err(ExifError::Unwritable("BigTIFF files can't be written yet"));
+    }
+    if !exif.confidence().evidence.contains(&Evidence::Structural) {
+        return Err(ExifError::Unwritable("the exif was found by scanning for it, not where the file says it is"));
+    }
+    match exif.file_type() {
+        FileType::Jpeg | FileType::Tiff | FileType::Cr2 => Ok(()),
+        _ => Err(ExifError::Unwritable("only jpeg and tiff files can be written"))
+    }
+}
+
+// Every directory we know how to write, as found. Raw SubIFDs are left alone, and keep their
+// pointers verbatim.
+fn read_tables(exif: &Exif) -> Vec<Table> {
+    let byte_order = exif.byte_order();
+    let variant = exif.variant();
+    let writable = |kind: IfdKind| matches!(kind, IfdKind::Ifd(_) | IfdKind::Exif | IfdKind::Gps | IfdKind::Interop);
+    exif.directories().filter(|directory| writable(directory.kind)).map(|directory| {
+        let entries = directory.entries.iter().map(|entry| {
+            let pointer = POINTERS.iter()
+                .find(|(parent, tag, child)| *parent == directory.kind && tag.id() == entry.tag && exif.directory(*child).is_some());
+            let value = match pointer {
+                Some(&(_, _, child)) => Value::Pointer(child),
+                None => {
+                    let mut field = [0; 4];
+                    field.copy_from_slice(&entry.inline_bytes(byte_order));
+                    Value::Kept(field)
+                }
+            };
+            Entry { tag: entry.tag, tag_type: entry.tag_type, count: entry.count as u32, value }
+        }).collect::<Vec<Entry>>();
+        let end = directory.entry_offset(entries.len(), variant) + variant.offset_size();
+        Table { kind: directory.kind, entries, old: Some(directory.offset..end), dirty: false }
+    }).collect()
+}
+
+// The table for a directory, creating it (and its pointer) if it's a sub-directory we don't have.
+fn table_for(tables: &mut Vec<Table>, kind: IfdKind) -> Result<usize> {
+    if let Some(i) = tables.iter().position(|table| table.kind == kind) {
+        return Ok(i);
+    }
+    let &(parent, pointer, _) = POINTERS.iter().find(|(_, _, child)| *child == kind)
+        .ok_or(ExifError::Unwritable("there's no such directory to add to"))?;
+    let parent = table_for(tables, parent)?;
+    insert(&mut tables[parent].entries, Entry { tag: pointer.id(), tag_type: 4, count: 1, value: Value::Pointer(kind) });
+    tables[parent].dirty = true;
+    tables.push(Table { kind, entries: Vec::new(), old: None, dirty: true });
+    Ok(tables.len() - 1)
+}
+
+// Keep entries in tag order, which the spec requires.
+fn insert(entries: &mut Vec<Entry>, entry: Entry) {
+    let at = entries.iter().position(|e| e.tag > entry.tag).unwrap_or(entries.len());
+    entries.insert(at, entry);
+}
+
+fn set(tables: &mut Vec<Table>, kind: IfdKind, tag: TagId, value: &TagValue, byte_order: ByteOrder, erase: &mut Vec<Range<usize>>) -> Result<()> {
+    if MANAGED.contains(&tag) {
+        return Err(ExifError::Unwritable("that tag holds an offset, which we look after ourselves"));
+    }
+    let (tag_type, count, data) = value.encode(byte_order);
+    if type_size(tag_type).is_none() || tag_type > 12 {
+        return Err(ExifError::Unwritable("64 bit values only fit in a BigTIFF"));
+    }
+    let count = u32::try_from(count).map_err(|_| ExifError::Unwritable("the value is too long"))?;
+    remove(tables, kind, tag, byte_order, erase)?;
+    let i = table_for(tables, kind)?;
+    insert(&mut tables[i].entries, Entry { tag: tag.id(), tag_type, count, value: Value::New(data) });
+    tables[i].dirty = true;
+    Ok(())
+}
+
+fn remove(tables: &mut Vec<Table>, kind: IfdKind, tag: TagId, byte_order: ByteOrder, erase: &mut Vec<Range<usize>>) -> Result<()> {
+    if let Some(&(_, _, child)) = POINTERS.iter().find(|(parent, pointer, _)| *parent == kind && *pointer == tag) {
+        return remove_directory(tables, child, byte_order, erase);
+    }
+    if MANAGED.contains(&tag) {
+        return Err(ExifError::Unwritable("that tag holds an offset, which we look after ourselves"));
+    }
+    if let Some(table) = tables.iter_mut().find(|table| table.kind == kind) {
+        let before = table.entries.len();
+        erase.extend(table.entries.iter().filter(|entry| entry.tag == tag.id()).filter_map(|entry| entry.old_data(byte_order)));
+        table.entries.retain(|entry| entry.tag != tag.id());
+        table.dirty |= table.entries.len() != before;
+    }
+    Ok(())
+}
+
+// Erase a sub-directory, and any of its own, and drop the pointer to it.
+fn remove_directory(tables: &mut Vec<Table>, kind: IfdKind, byte_order: ByteOrder, erase: &mut Vec<Range<usize>>) -> Result<()> {
+    let &(parent, pointer, _) = POINTERS.iter().find(|(_, _, child)| *child == kind)
+        .ok_or(ExifError::Unwritable("only the Exif, GPS and Interop directories can be removed"))?;
+    for &(_, _, grandchild) in POINTERS.iter().filter(|(p, _, _)| *p == kind) {
+        remove_directory(tables, grandchild, byte_order, erase)?;
+    }
+    if let Some(i) = tables.iter().position(|table| table.kind == kind) {
+        let table = tables.remove(i);
+        erase.extend(table.entries.iter().filter_map(|entry| entry.old_data(byte_order)));
+        erase.extend(table.old);
+    }
+    if let Some(parent) = tables.iter_mut().find(|table| table.kind == parent) {
+        let before = parent.entries.len();
+        parent.entries.retain(|entry| entry.tag != pointer.id());
+        parent.dirty |= parent.entries.len() != before;
+    }
+    Ok(())
+}
+
+// A directory that points at one that's moving has to be written again too, and so on up.
+fn mark_parents(tables: &mut [Table]) {
+    loop {
+        let dirty: Vec<IfdKind> = tables.iter().filter(|table| table.dirty).map(|table| table.kind).collect();
+        let mut changed = false;
+        for table in tables.iter_mut().filter(|table| !table.dirty) {
+            let points_at_dirty = table.entries.iter().any(|entry| matches!(entry.value, Value::Pointer(kind) if dirty.contains(&kind)));
+            let next_is_dirty = matches!(table.kind, IfdKind::Ifd(n) if dirty.contains(&IfdKind::Ifd(n + 1)));
+            if points_at_dirty || next_is_dirty {
+                table.dirty = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+    }
+}
+
+fn offset_of(offsets: &[(IfdKind, usize)], kind: IfdKind) -> Option<usize> {
+    offsets.iter().find(|(k, _)| *k == kind).map(|(_, offset)| *offset)
+}
+
+// Append a directory to the end of tiff, followed by whichever of its values don't fit in their
+// entries, and hand back where it starts. Offsets have to be even, so we pad to keep them that way.
+fn write_table(tiff: &mut Vec<u8>, table: &Table, offsets: &[(IfdKind, usize)], next: usize, byte_order: ByteOrder) -> Result<usize> {
+    if tiff.len() % 2 == 1 {
+        tiff.push(0);
+    }
+    let start = tiff.len();
+    let count = u16::try_from(table.entries.len()).map_err(|_| ExifError::Unwritable("too many entries for one directory"))?;
+    let data_start = start + 2 + 12 * table.entries.len() + 4;
+    let offset = |offset: usize| -> Result<[u8; 4]> {
+        u32::try_from(offset).map(|offset| byte_order.u32_bytes(offset)).map_err(|_| ExifError::Unwritable("the exif would be over 4GB"))
+    };
+
+    let mut directory = byte_order.u16_bytes(count).to_vec();
+    let mut data = Vec::new();
+    for entry in &table.entries {
+        directory.extend_from_slice(&byte_order.u16_bytes(entry.tag));
+        directory.extend_from_slice(&byte_order.u16_bytes(entry.tag_type));
+        directory.extend_from_slice(&byte_order.u32_bytes(entry.count));
+        let field = match &entry.value {
+            Value::Kept(field) => *field,
+            Value::Pointer(kind) => offset(offset_of(offsets, *kind).ok_or(ExifError::InvalidOffset("sub-IFD"))?)?,
+            // Small values are left-justified, and the rest of the field is padding.
+            Value::New(bytes) if bytes.len() <= 4 => {
+                let mut field = [0; 4];
+                field[..bytes.len()].copy_from_slice(bytes);
+                field
+            },
+            Value::New(bytes) => {
+                let at = offset(data_start + data.len())?;
+                data.extend_from_slice(bytes);
+                if data.len() % 2 == 1 {
+                    data.push(0);
+                }
+                at
+            }
+        };
+        directory.extend_from_slice(&field);
+    }
+    directory.extend_from_slice(&offset(next)?);
+    tiff.extend_from_slice(&directory);
+    tiff.extend_from_slice(&data);
+    offset(tiff.len())?;
+    Ok(start)
+}
+
+// Put the new tiff data back in the file it came from. In a jpeg, that's a new APP1 segment in
+// place of the old one. A tiff file is nothing but tiff data, so it's the whole file.
+fn splice(exif: &Exif, tiff: &[u8]) -> Result<Vec<u8>> {
+    let buf = exif.bytes();
+    match exif.file_type() {
+        FileType::Jpeg => {
+            // The marker, length and signature come right before the tiff header.
+            let start = exif.tiff_header_offset() - EXIF_SIGNATURE.len() - 4;
+            let end = exif.tiff_header_offset() + exif.tiff().len();
+            // The length counts itself, and a segment can't be longer than it can say.
+            let length = u16::try_from(2 + EXIF_SIGNATURE.len() + tiff.len())
+                .map_err(|_| ExifError::Unwritable("the exif won't fit in a jpeg's 64KB APP1 segment"))?;
+            let mut out = buf[..start].to_vec();
+            out.extend_from_slice(&[0xff, APP1]);
+            out.extend_from_slice(&length.to_be_bytes());
+            out.extend_from_slice(EXIF_SIGNATURE);
+            out.extend_from_slice(tiff);
+            out.extend_from_slice(&buf[end..]);
+            Ok(out)
+        },
+        _ => Ok(tiff.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::ParseMode;
+    use crate::ifd::IFD;
+    use crate::rational::Rational;
+    use std::fs;
+
+    const LE_TIF: &[u8] = include_bytes!("../fixtures/le.tif");
+    const BE_TIF: &[u8] = include_bytes!("../fixtures/be.tif");
+    const EXIF_JPG: &[u8] = include_bytes!("../fixtures/exif.jpg");
+    const BIGTIFF: &[u8] = include_bytes!("../fixtures/bigtiff.tif");
+
+    fn read(buf: &[u8]) -> Exif {
+        Exif::from_bytes_with(buf, ParseMode::Strict).unwrap()
+    }
+
+    fn text(exif: &Exif, tag: TagId) -> Option<String> {
+        exif.value_string(exif.entry(tag)?).ok()
+    }
+
+    // Save to a file and read it back, strictly, so anything we wrote that's off spec fails.
+    fn save_and_read(exif: &Exif, name: &str) -> Exif {
+        let path = std::env::temp_dir().join(format!("helloexif-{}-{}", std::process::id(), name));
+        exif.save(&path).unwrap();
+        let saved = Exif::from_path_with(&path, ParseMode::Strict);
+        fs::remove_file(&path).unwrap();
+        saved.unwrap()
+    }
+
+    #[test]
+    fn set_save_and_read_back() {
+        for (name, fixture) in [("le.tif", LE_TIF), ("be.tif", BE_TIF), ("exif.jpg", EXIF_JPG)] {
+            let mut exif = read(fixture);
+            exif.set(TagId::Artist, "Jane Doe").unwrap();
+            exif.set(TagId::DateTimeOriginal, "2008:05:30 15:56:01").unwrap();
+            let saved = save_and_read(&exif, name);
+            assert_eq!((saved.file_type(), saved.byte_order()), (exif.file_type(), exif.byte_order()), "{}", name);
+            assert_eq!(text(&saved, TagId::Make).as_deref(), Some("HelloExif"), "{}", name);
+            assert_eq!(text(&saved, TagId::Artist).as_deref(), Some("Jane Doe"), "{}", name);
+            assert_eq!(text(&saved, TagId::DateTimeOriginal).as_deref(), Some("2008:05:30 15:56:01"), "{}", name);
+            assert!(saved.exif_ifd().is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn values_move_out_of_line_when_they_grow() {
+        for fixture in [LE_TIF, BE_TIF, EXIF_JPG] {
+            let mut exif = read(fixture);
+            exif.set(TagId::Artist, "Jo").unwrap();
+            assert_eq!(exif.entry(TagId::Artist).unwrap().data_offset(), None);
+            exif.set(TagId::Artist, "Josephine Bloggs").unwrap();
+            assert!(exif.entry(TagId::Artist).unwrap().data_offset().is_some());
+            let saved = save_and_read(&exif, "grown");
+            assert_eq!(text(&saved, TagId::Artist).as_deref(), Some("Josephine Bloggs"));
+            assert_eq!(text(&saved, TagId::Make).as_deref(), Some("HelloExif"));
+        }
+    }
+
+    #[test]
+    fn unedited_entries_are_copied_byte_for_byte() {
+        for (name, fixture) in [("le.tif", LE_TIF), ("be.tif", BE_TIF), ("exif.jpg", EXIF_JPG)] {
+            let original = read(fixture);
+            let out = rewrite(&original, &[Edit::Set(IfdKind::Ifd(0), TagId::Artist, "Jane Doe".into())]).unwrap();
+            let saved = read(&out);
+            for entry in &original.directory(IfdKind::Ifd(0)).unwrap().entries {
+                let copy = saved.directory(IfdKind::Ifd(0)).unwrap().entries.iter().find(|e| e.tag == entry.tag).unwrap();
+                let fields = |e: &IFD| (e.tag, e.tag_type, e.count, e.value_offset);
+                assert_eq!(fields(copy), fields(entry), "{}", name);
+                if let Some(offset) = entry.data_offset() {
+                    let range = offset as usize..(offset + entry.data_length().unwrap()) as usize;
+                    assert_eq!(saved.tiff()[range.clone()], original.tiff()[range], "{}", name);
+                }
+            }
+            // In a jpeg, nothing outside the APP1 segment moves either.
+            let end = original.tiff_header_offset() + original.tiff().len();
+            let grown = saved.bytes().len() - original.bytes().len();
+            assert_eq!(saved.bytes()[end + grown..], original.bytes()[end..], "{}", name);
+        }
+    }
+
+    #[test]
+    fn replaced_values_are_zeroed() {
+        let mut exif = read(LE_TIF);
+        exif.set(TagId::Make, "Someone Else").unwrap();
+        let saved = save_and_read(&exif, "replaced");
+        assert_eq!(text(&saved, TagId::Make).as_deref(), Some("Someone Else"));
+        assert!(!saved.tiff().windows(9).any(|bytes| bytes == b"HelloExif"));
+    }
+
+    #[test]
+    fn removed_values_are_zeroed() {
+        let latitude = TagValue::Rational(vec![Rational::new(51, 1), Rational::new(30, 1), Rational::new(2613, 100)]);
+        let mut exif = read(LE_TIF);
+        exif.set(TagId::GPSLatitudeRef, "N").unwrap();
+        exif.set(TagId::GPSLatitude, latitude.clone()).unwrap();
+        let with_gps = save_and_read(&exif, "with-gps");
+        let gps = with_gps.directory(IfdKind::Gps).unwrap();
+        let directory = gps.offset..gps.entry_offset(gps.entries.len(), TiffVariant::Classic) + 4;
+        let (_, _, data) = latitude.encode(ByteOrder::LittleEndian);
+        assert!(with_gps.tiff().windows(data.len()).any(|bytes| bytes == data.as_slice()));
+
+        let mut exif = with_gps;
+        exif.remove_tag(TagId::GPSInfoIFDPointer).unwrap();
+        let saved = save_and_read(&exif, "without-gps");
+        assert!(saved.gps_ifd().is_none());
+        assert!(!saved.tiff().windows(data.len()).any(|bytes| bytes == data.as_slice()));
+        assert!(saved.tiff()[directory].iter().all(|&b| b == 0));
+        assert_eq!(text(&saved, TagId::Make).as_deref(), Some("HelloExif"));
+    }
+
+    #[test]
+    fn removes_tags_in_sub_directories_and_the_directories_themselves() {
+        let mut exif = read(LE_TIF);
+        exif.set(TagId::DateTimeOriginal, "2008:05:30 15:56:01").unwrap();
+        exif.set(TagId::UserComment, TagValue::Undefined(b"ASCII\0\0\0hello".to_vec().into())).unwrap();
+        exif.set(TagId::GPSLatitudeRef, "N").unwrap();
+
+        // Taking a tag out of the Exif IFD leaves the rest of it.
+        exif.remove_tag(TagId::DateTimeOriginal).unwrap();
+        let saved = save_and_read(&exif, "removed-tag");
+        assert_eq!(text(&saved, TagId::DateTimeOriginal), None);
+        assert!(saved.entry(TagId::UserComment).is_some());
+
+        // Taking out a pointer takes its directory with it.
+        exif.remove_tag(TagId::GPSInfoIFDPointer).unwrap();
+        let saved = save_and_read(&exif, "removed-pointer");
+        assert!(saved.gps_ifd().is_none());
+        assert!(saved.exif_ifd().is_some());
+        assert_eq!(text(&saved, TagId::Make).as_deref(), Some("HelloExif"));
+    }
+
+    #[test]
+    fn refuses_exif_too_big_for_a_jpeg() {
+        let mut exif = read(EXIF_JPG);
+        let description = "x".repeat(70000);
+        assert!(matches!(exif.set(TagId::ImageDescription, description.as_str()), Err(ExifError::Unwritable(_))));
+        // Nothing changed.
+        assert_eq!(exif.bytes(), EXIF_JPG);
+        // A tiff has no such limit.
+        let mut exif = read(LE_TIF);
+        exif.set(TagId::ImageDescription, description.as_str()).unwrap();
+        assert_eq!(text(&exif, TagId::ImageDescription).map(|s| s.len()), Some(70000));
+    }
+
+    #[test]
+    fn refuses_bigtiffs() {
+        let mut exif = read(BIGTIFF);
+        assert!(matches!(exif.set(TagId::Artist, "Jane Doe"), Err(ExifError::Unwritable(_))));
+        assert!(matches!(exif.remove_tag(TagId::Make), Err(ExifError::Unwritable(_))));
+        assert_eq!(exif.bytes(), BIGTIFF);
+    }
+}